@@ -0,0 +1,278 @@
+use std::str;
+
+use collection_traits::*;
+
+use bincode;
+
+use super::entity::Entity;
+use super::entity_manager::EntityManager;
+use super::error::Error;
+
+
+pub const FORMAT_VERSION: u32 = 1u32;
+
+static MAGIC: &'static [u8; 4] = b"ECSB";
+
+
+// layout, all integers little endian
+//   magic "ECSB", format version u32,
+//   entities length u64 followed by the bincode encoded entities,
+//   component count u32, then for each component
+//   name length u32, name utf8, schema version u32, payload length u64, bincode encoded payload
+impl EntityManager {
+    pub fn to_binary(&self) -> Result<Vec<u8>, Error> {
+        let components = self.try_components()?;
+        let entities = self.try_entities()?;
+        let component_managers = components.component_managers();
+        let serde_components = components.serde_registry().sorted();
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(MAGIC);
+        write_u32(&mut bytes, FORMAT_VERSION);
+
+        match bincode::serialize(&*entities) {
+            Ok(encoded) => write_bytes(&mut bytes, &encoded),
+            Err(error) => return Err(Error::Serialization(error.to_string())),
+        }
+
+        let count = serde_components.iter()
+            .filter(|&&(type_id, _)| component_managers.contains_key(type_id))
+            .count();
+        write_u32(&mut bytes, count as u32);
+
+        for (type_id, serde_component) in serde_components {
+            if let Some(component_manager) = component_managers.get(type_id) {
+                let name = serde_component.name().as_bytes();
+
+                write_u32(&mut bytes, name.len() as u32);
+                bytes.extend_from_slice(name);
+                write_u32(&mut bytes, serde_component.version());
+                write_bytes(&mut bytes, &serde_component.encode(&**component_manager)?);
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    // replaces the entities and serde registered component storages, keeping entity ids,
+    // entity names and groups are not part of the format and are cleared,
+    // component payloads older than their registered version run through registered migrations,
+    // everything is decoded before any storage is touched so errors leave the world as it was
+    pub fn load_binary(&self, bytes: &[u8]) -> Result<(), Error> {
+        let components = self.try_components_mut()?;
+        let mut entities = self.try_entities_mut()?;
+        let component_managers = components.component_managers();
+        let registry = components.serde_registry();
+        let mut reader = Reader::new(bytes);
+
+        if reader.read(MAGIC.len())? != &MAGIC[..] {
+            return Err(Error::Serialization("invalid binary snapshot header".to_owned()));
+        }
+        let format_version = reader.read_u32()?;
+        if format_version != FORMAT_VERSION {
+            return Err(Error::Serialization(format!("unsupported binary format version {}", format_version)));
+        }
+
+        let list: Vec<Entity> = match bincode::deserialize(reader.read_bytes()?) {
+            Ok(list) => list,
+            Err(error) => return Err(Error::Serialization(error.to_string())),
+        };
+
+        let mut decoded = Vec::new();
+        for _ in 0..reader.read_u32()? {
+            let name_len = reader.read_u32()? as usize;
+            let name = match str::from_utf8(reader.read(name_len)?) {
+                Ok(name) => name,
+                Err(error) => return Err(Error::Serialization(error.to_string())),
+            };
+            let version = reader.read_u32()?;
+            let payload = reader.read_bytes()?;
+
+            let type_id = match registry.type_id(name) {
+                Some(type_id) => *type_id,
+                None => return Err(Error::Serialization(format!("unknown component {}", name))),
+            };
+            if !component_managers.contains_key(&type_id) {
+                return Err(Error::Serialization(format!("unregistered component {}", name)));
+            }
+
+            let serde_component = match registry.get(&type_id) {
+                Some(serde_component) => serde_component,
+                None => return Err(Error::Serialization(format!("unknown component {}", name))),
+            };
+            let payload = registry.migrate(&type_id, version, payload.to_vec())?;
+
+            decoded.push((type_id, serde_component.decode(&payload)?));
+        }
+
+        for (type_id, _) in registry.components().iter() {
+            if let Some(component_manager) = component_managers.get(type_id) {
+                component_manager.clear()?;
+            }
+        }
+        for (type_id, components) in decoded {
+            if let (Some(component_manager), Some(serde_component)) = (component_managers.get(&type_id), registry.get(&type_id)) {
+                serde_component.replace(&**component_manager, components);
+            }
+        }
+
+        entities.clear();
+        for entity in list {
+            entities.insert(entity);
+        }
+
+        Ok(())
+    }
+}
+
+
+#[inline]
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+#[inline]
+fn write_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    bytes.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader {
+            bytes: bytes,
+            offset: 0usize,
+        }
+    }
+
+    #[inline]
+    fn read(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.bytes.len() - self.offset {
+            Err(Error::Serialization("unexpected end of binary snapshot".to_owned()))
+        } else {
+            let bytes = &self.bytes[self.offset..(self.offset + len)];
+            self.offset += len;
+            Ok(bytes)
+        }
+    }
+    #[inline]
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut value = [0u8; 4];
+        value.copy_from_slice(self.read(4usize)?);
+        Ok(u32::from_le_bytes(value))
+    }
+    #[inline]
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(self.read(8usize)?);
+        Ok(u64::from_le_bytes(value))
+    }
+    #[inline]
+    fn read_bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.read_u64()?;
+        if len > (self.bytes.len() - self.offset) as u64 {
+            Err(Error::Serialization("unexpected end of binary snapshot".to_owned()))
+        } else {
+            self.read(len as usize)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use component::Component;
+    use component_manager::HashMapComponentManager;
+    use entity_allocator::{SequentialAllocator, SeededAllocator};
+
+    use std::sync::Arc;
+
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Health(u32);
+
+    impl Component for Health {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+
+    #[test]
+    fn test_binary_migration() {
+        let entity_manager = EntityManager::new();
+        entity_manager.register_serde_component_versioned::<Health>("health", 0u32);
+
+        let entity = entity_manager.create_entity();
+        entity_manager.insert_component(entity, Health(10));
+        let bytes = entity_manager.to_binary().unwrap();
+
+        entity_manager.register_serde_component_versioned::<Health>("health", 1u32);
+        entity_manager.register_component_migration::<Health, _>(0u32, |bytes: &[u8]| {
+            let old: Vec<(Entity, Health)> = bincode::deserialize(bytes).unwrap();
+            let new: Vec<(Entity, Health)> = old.into_iter()
+                .map(|(entity, health)| (entity, Health(health.0 * 10)))
+                .collect();
+            Ok(bincode::serialize(&new).unwrap())
+        });
+
+        entity_manager.remove_component::<Health>(&entity);
+        entity_manager.load_binary(&bytes).unwrap();
+
+        assert!(entity_manager.is_entity_alive(&entity));
+        assert_eq!(entity_manager.remove_component::<Health>(&entity), Some(Health(100)));
+    }
+
+    #[test]
+    fn test_binary_decode_error() {
+        let entity_manager = EntityManager::new();
+        entity_manager.register_serde_component_versioned::<Health>("health", 0u32);
+
+        let entity = entity_manager.create_entity();
+        entity_manager.insert_component(entity, Health(10));
+        let bytes = entity_manager.to_binary().unwrap();
+
+        entity_manager.register_serde_component_versioned::<Health>("health", 1u32);
+        entity_manager.register_component_migration::<Health, _>(0u32, |_: &[u8]| Ok(vec![1u8]));
+
+        assert!(entity_manager.load_binary(&bytes).is_err());
+        assert!(entity_manager.is_entity_alive(&entity));
+        assert_eq!(entity_manager.remove_component::<Health>(&entity), Some(Health(10)));
+    }
+
+    #[test]
+    fn test_binary_load_then_create() {
+        let entity_manager = EntityManager::new_with_allocator(Arc::new(SequentialAllocator::new()));
+        let saved = entity_manager.create_entity();
+        let bytes = entity_manager.to_binary().unwrap();
+
+        let loaded = EntityManager::new_with_allocator(Arc::new(SequentialAllocator::new()));
+        loaded.load_binary(&bytes).unwrap();
+
+        let created = loaded.create_entity();
+        assert_ne!(created, saved);
+        assert_eq!(loaded.entity_count(), 2usize);
+    }
+
+    #[test]
+    fn test_binary_seeded_load_then_build() {
+        let entity_manager = EntityManager::new_with_allocator(Arc::new(SeededAllocator::new(3u64)));
+        entity_manager.register_serde_component::<Health>("health");
+        let saved = entity_manager.build_entity().with(Health(10)).build();
+        let bytes = entity_manager.to_binary().unwrap();
+
+        let loaded = EntityManager::new_with_allocator(Arc::new(SeededAllocator::new(3u64)));
+        loaded.register_serde_component::<Health>("health");
+        loaded.load_binary(&bytes).unwrap();
+
+        let built = loaded.build_entity().with(Health(20)).build();
+        assert_ne!(built, saved);
+        assert_eq!(loaded.entity_count(), 2usize);
+        assert_eq!(loaded.remove_component::<Health>(&saved), Some(Health(10)));
+    }
+}
@@ -0,0 +1,117 @@
+use super::component::Component;
+use super::components::Components;
+use super::entity::Entity;
+use super::error::Error;
+
+
+pub trait Bundle: Send + Sync + 'static {
+    fn is_registered(&Components) -> bool;
+    fn register(&mut Components);
+    // all components of the bundle must already be registered
+    fn insert_into(self, &Components, Entity) -> Result<(), Error>;
+}
+
+
+macro_rules! impl_bundle_tuple {
+    ($($name: ident),+) => (
+        impl<$($name: Component),+> Bundle for ($($name,)+) {
+            #[inline]
+            fn is_registered(components: &Components) -> bool {
+                $(components.is_registered::<$name>())&&+
+            }
+            #[inline]
+            fn register(components: &mut Components) {
+                $(components.register::<$name>();)+
+            }
+            #[inline]
+            #[allow(non_snake_case)]
+            fn insert_into(self, components: &Components, entity: Entity) -> Result<(), Error> {
+                let ($($name,)+) = self;
+                $(components.try_insert(entity, $name)?;)+
+                Ok(())
+            }
+        }
+    );
+}
+
+impl_bundle_tuple!(A);
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+
+#[macro_export]
+macro_rules! impl_bundle {
+    ($name: ident { $($field: ident: $ty: ty),+ $(,)* }) => (
+        impl $crate::Bundle for $name {
+            #[inline]
+            fn is_registered(components: &$crate::Components) -> bool {
+                $(components.is_registered::<$ty>())&&+
+            }
+            #[inline]
+            fn register(components: &mut $crate::Components) {
+                $(components.register::<$ty>();)+
+            }
+            #[inline]
+            fn insert_into(self, components: &$crate::Components, entity: $crate::Entity) -> Result<(), $crate::Error> {
+                $(components.try_insert::<$ty>(entity, self.$field)?;)+
+                Ok(())
+            }
+        }
+    );
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use component_manager::HashMapComponentManager;
+    use entity_manager::EntityManager;
+
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Position(i32, i32);
+
+    impl Component for Position {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Health(u32);
+
+    impl Component for Health {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+    pub struct Enemy {
+        position: Position,
+        health: Health,
+    }
+
+    impl_bundle!(Enemy { position: Position, health: Health });
+
+
+    #[test]
+    fn test_bundle() {
+        let entity_manager = EntityManager::new();
+
+        let a = entity_manager.spawn((Position(1, 2), Health(10)));
+        let b = entity_manager.spawn(Enemy {
+            position: Position(3, 4),
+            health: Health(20),
+        });
+
+        assert_eq!(entity_manager.remove_component::<Position>(&a), Some(Position(1, 2)));
+        assert_eq!(entity_manager.remove_component::<Health>(&a), Some(Health(10)));
+        assert_eq!(entity_manager.remove_component::<Position>(&b), Some(Position(3, 4)));
+        assert_eq!(entity_manager.remove_component::<Health>(&b), Some(Health(20)));
+    }
+}
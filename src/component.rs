@@ -1,8 +1,17 @@
 use std::any::Any;
 
+use vector::Vector;
+
 use super::component_manager::ComponentManager;
+use super::requirement::Requirement;
 
 
 pub trait Component: Sized + Any + Send + Sync {
     type ComponentManager: ComponentManager<Self> + Any + Send + Sync;
+
+    // components inserted with their defaults when this one is inserted without them
+    #[inline]
+    fn required() -> Vector<Requirement> {
+        Vector::new()
+    }
 }
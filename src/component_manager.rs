@@ -7,7 +7,10 @@ use vector::Vector;
 use hash_map::HashMap;
 
 use super::component::Component;
+use super::entities::Entities;
 use super::entity::Entity;
+use super::entity_map::EntityMap;
+use super::lifecycle_hooks::LifecycleHooks;
 
 
 pub trait ComponentManager<T: Component>: Sized + Any + Send + Sync {
@@ -19,6 +22,13 @@ pub trait ComponentManager<T: Component>: Sized + Any + Send + Sync {
     fn get(&self, entity: &Entity) -> Option<&Atomic<T>>;
 
     fn contains(&self, entity: &Entity) -> bool;
+    fn entities(&self) -> Vector<Entity>;
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = (Entity, &'a Atomic<T>)> + 'a>;
+    fn len(&self) -> usize;
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     fn insert(&mut self, entity: Entity, component: T);
     fn remove(&mut self, entity: &Entity) -> Option<T>;
 
@@ -28,6 +38,117 @@ pub trait ComponentManager<T: Component>: Sized + Any + Send + Sync {
 
 pub struct WrappedComponentManager<T: Component> {
     inner: T::ComponentManager,
+    clone: Option<fn(&T) -> T>,
+    map_entities: Option<fn(&mut T, &EntityMap)>,
+    hooks: LifecycleHooks<T>,
+}
+
+impl<T: Component> WrappedComponentManager<T> {
+    #[inline]
+    pub fn set_clone(&mut self, clone: fn(&T) -> T) {
+        self.clone = Some(clone);
+    }
+    #[inline]
+    pub fn set_map_entities(&mut self, map_entities: fn(&mut T, &EntityMap)) {
+        self.map_entities = Some(map_entities);
+    }
+    #[inline]
+    pub fn set_hooks(&mut self, hooks: LifecycleHooks<T>) {
+        self.hooks = hooks;
+    }
+    #[inline]
+    pub fn hooks(&self) -> &LifecycleHooks<T> {
+        &self.hooks
+    }
+    #[inline]
+    pub fn clone_fn(&self) -> Option<fn(&T) -> T> {
+        self.clone
+    }
+    #[inline]
+    pub fn is_cloneable(&self) -> bool {
+        self.clone.is_some()
+    }
+    #[inline]
+    pub fn clone_component(&self, entity: &Entity) -> Option<T> {
+        match self.clone {
+            Some(clone) => self.get(entity).map(|component| clone(component.as_ref())),
+            None => None,
+        }
+    }
+    pub fn snapshot(&self) -> Option<Vector<(Entity, T)>> {
+        match self.clone {
+            Some(clone) => {
+                let entities = self.entities();
+                let mut snapshot = Vector::with_capacity(entities.len());
+
+                for entity in entities {
+                    if let Some(component) = self.get(&entity) {
+                        snapshot.push((entity, clone(component.as_ref())));
+                    }
+                }
+
+                Some(snapshot)
+            },
+            None => None,
+        }
+    }
+    // runs on_remove for the old entities and on_insert for the mapped ones
+    pub fn map_entities(&mut self, entity_map: &EntityMap) {
+        let entities = self.inner.entities();
+        let mut components = Vector::with_capacity(entities.len());
+
+        for entity in entities {
+            if let Some(mut component) = self.remove(&entity) {
+                if let Some(map_entities) = self.map_entities {
+                    map_entities(&mut component, entity_map);
+                }
+                components.push((entity_map.map(entity), component));
+            }
+        }
+        for (entity, component) in components {
+            self.insert(entity, component);
+        }
+    }
+    // clones the components of every mapped from entity onto its to entity
+    pub fn clone_entities(&mut self, entity_map: &EntityMap) {
+        if let Some(clone) = self.clone {
+            for (from, to) in entity_map.pairs() {
+                let component = match self.inner.get(&from) {
+                    Some(component) => {
+                        let mut component = clone(component.as_ref());
+                        if let Some(map_entities) = self.map_entities {
+                            map_entities(&mut component, entity_map);
+                        }
+                        component
+                    },
+                    None => continue,
+                };
+                self.insert(to, component);
+            }
+        }
+    }
+    // drops the components of entities that are not alive
+    pub fn retain_entities(&mut self, entities: &Entities) {
+        for entity in self.inner.entities() {
+            if !entities.is_alive(&entity) {
+                self.remove(&entity);
+            }
+        }
+    }
+    pub fn restore(&mut self, snapshot: &[(Entity, T)]) -> bool {
+        match self.clone {
+            Some(clone) => {
+                self.clear();
+
+                for &(entity, ref component) in snapshot {
+                    self.insert(entity, clone(component));
+                }
+
+                true
+            },
+            None => false,
+        }
+    }
 }
 
 impl<T: Component> ComponentManager<T> for WrappedComponentManager<T> {
@@ -35,10 +156,18 @@ impl<T: Component> ComponentManager<T> for WrappedComponentManager<T> {
     fn new() -> WrappedComponentManager<T> {
         WrappedComponentManager {
             inner: ComponentManager::new(),
+            clone: None,
+            map_entities: None,
+            hooks: LifecycleHooks::new(),
         }
     }
     #[inline]
     fn clear(&mut self) {
+        if !self.hooks.is_empty() {
+            for entity in self.inner.entities() {
+                self.remove(&entity);
+            }
+        }
         self.inner.clear();
     }
     #[inline]
@@ -50,12 +179,39 @@ impl<T: Component> ComponentManager<T> for WrappedComponentManager<T> {
         self.inner.contains(entity)
     }
     #[inline]
+    fn entities(&self) -> Vector<Entity> {
+        self.inner.entities()
+    }
+    #[inline]
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = (Entity, &'a Atomic<T>)> + 'a> {
+        self.inner.iter()
+    }
+    #[inline]
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
     fn insert(&mut self, entity: Entity, component: T) {
+        if self.inner.contains(&entity) {
+            if let Some(replaced) = self.inner.remove(&entity) {
+                self.hooks.replaced(&entity, &replaced);
+            }
+        }
+
         self.inner.insert(entity, component);
+
+        if let Some(component) = self.inner.get(&entity) {
+            self.hooks.inserted(&entity, component.as_ref());
+        }
     }
     #[inline]
     fn remove(&mut self, entity: &Entity) -> Option<T> {
-        self.inner.remove(entity)
+        let component = self.inner.remove(entity);
+
+        if let Some(ref component) = component {
+            self.hooks.removed(entity, component);
+        }
+
+        component
     }
     #[inline]
     fn replace(&mut self) {
@@ -63,10 +219,11 @@ impl<T: Component> ComponentManager<T> for WrappedComponentManager<T> {
     }
 }
 
+// dropping storage does not run on_remove hooks
 impl<T: Component> Drop for WrappedComponentManager<T> {
     #[inline]
     fn drop(&mut self) {
-        self.clear();
+        self.inner.clear();
     }
 }
 
@@ -99,15 +256,24 @@ impl<T: Component> ComponentManager<T> for HashMapComponentManager<T> {
         self.map.contains_key(entity)
     }
     #[inline]
+    fn entities(&self) -> Vector<Entity> {
+        self.map.keys().cloned().collect()
+    }
+    #[inline]
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = (Entity, &'a Atomic<T>)> + 'a> {
+        Box::new(self.map.iter().map(|(entity, component)| (*entity, component)))
+    }
+    #[inline]
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+    #[inline]
     fn insert(&mut self, entity: Entity, component: T) {
         self.map.insert(entity, Atomic::new(component));
     }
     #[inline]
     fn remove(&mut self, entity: &Entity) -> Option<T> {
-        match self.map.remove(entity) {
-            Some(component) => Some(component.take()),
-            None => None,
-        }
+        self.map.remove(entity).map(|component| component.take())
     }
     #[inline]
     fn replace(&mut self) {
@@ -159,7 +325,22 @@ impl<T: Component> ComponentManager<T> for VecComponentManager<T> {
         self.index_of(entity).is_some()
     }
     #[inline]
+    fn entities(&self) -> Vector<Entity> {
+        self.vec.iter().map(|&(entity, _)| entity).collect()
+    }
+    #[inline]
+    fn iter<'a>(&'a self) -> Box<Iterator<Item = (Entity, &'a Atomic<T>)> + 'a> {
+        Box::new(self.vec.iter().map(|&(entity, ref component)| (entity, component)))
+    }
+    #[inline]
+    fn len(&self) -> usize {
+        self.vec.len()
+    }
+    #[inline]
     fn insert(&mut self, entity: Entity, component: T) {
+        if let Some(index) = self.index_of(&entity) {
+            self.vec.remove(index);
+        }
         self.vec.push((entity, Atomic::new(component)));
     }
     fn remove(&mut self, entity: &Entity) -> Option<T> {
@@ -1,16 +1,34 @@
-use std::any::{Any, TypeId};
+use std::any::{self, Any, TypeId};
 use std::sync::RwLock;
 
 use collection_traits::*;
 use hash_map::HashMap;
+use vector::Vector;
 
 use super::component::Component;
+use super::entities::Entities;
 use super::entity::Entity;
+use super::entity_map::{EntityMap, MapEntities};
+use super::error::Error;
 use super::component_manager::{ComponentManager, WrappedComponentManager};
+use super::lifecycle_hooks::LifecycleHooks;
+use super::prefab::{ClonedComponent, PrefabComponent};
+use super::requirement::Requirement;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use super::serialize::SerdeRegistry;
 
 
 pub struct Components {
     component_managers: HashMap<TypeId, Box<ComponentManagerLock>>,
+    requirements: HashMap<TypeId, Vector<Requirement>>,
+    required_by: HashMap<TypeId, Vector<TypeId>>,
+    #[cfg(feature = "serde")]
+    serde_registry: SerdeRegistry,
 }
 
 unsafe impl Send for Components {}
@@ -21,18 +39,116 @@ impl Components {
     pub fn new() -> Self {
         Components {
             component_managers: HashMap::new(),
+            requirements: HashMap::new(),
+            required_by: HashMap::new(),
+            #[cfg(feature = "serde")]
+            serde_registry: SerdeRegistry::new(),
         }
     }
 
-    pub fn register<T: Component>(&mut self) {
+    pub fn register<T: Component>(&mut self) -> bool {
+        let type_id = TypeId::of::<T>();
+
+        if self.component_managers.contains_key(&type_id) {
+            return false;
+        }
+        let requirements = T::required();
+        let mut path = Vector::new();
+        path.push((type_id, any::type_name::<T>()));
+        check_requirement_cycle(&mut path, &requirements);
+
         self.component_managers.insert(
-            TypeId::of::<T>(),
+            type_id,
             Box::new(RwLock::new(WrappedComponentManager::<T>::new()))
         );
+        for requirement in requirements.iter() {
+            requirement.register(self);
+
+            if let Some(required_by) = self.required_by.get_mut(&requirement.type_id()) {
+                required_by.push(type_id);
+                continue;
+            }
+            let mut required_by = Vector::new();
+            required_by.push(type_id);
+            self.required_by.insert(requirement.type_id(), required_by);
+        }
+        self.requirements.insert(type_id, requirements);
+        true
+    }
+    // registers the component if needed and replaces its hooks
+    pub fn register_with<T: Component>(&mut self, hooks: LifecycleHooks<T>) -> bool {
+        let registered = self.register::<T>();
+
+        match self.component_manager::<T>().write() {
+            Ok(mut component_manager) => component_manager.set_hooks(hooks),
+            Err(poisoned) => poisoned.into_inner().set_hooks(hooks),
+        }
+
+        registered
+    }
+    pub fn register_clone<T: Component + Clone>(&mut self) -> bool {
+        let registered = self.register::<T>();
+
+        match self.component_manager::<T>().write() {
+            Ok(mut component_manager) => component_manager.set_clone(clone_component::<T>),
+            Err(poisoned) => poisoned.into_inner().set_clone(clone_component::<T>),
+        }
+
+        registered
+    }
+    pub fn register_map_entities<T: Component + MapEntities>(&mut self) -> bool {
+        let registered = self.register::<T>();
+
+        match self.component_manager::<T>().write() {
+            Ok(mut component_manager) => component_manager.set_map_entities(map_entities::<T>),
+            Err(poisoned) => poisoned.into_inner().set_map_entities(map_entities::<T>),
+        }
+
+        registered
+    }
+    #[cfg(feature = "serde")]
+    pub fn register_serde<T>(&mut self, name: &'static str) -> bool
+        where T: Component + Serialize + DeserializeOwned
+    {
+        self.register_serde_versioned::<T>(name, 0u32)
+    }
+    #[cfg(feature = "serde")]
+    pub fn register_serde_versioned<T>(&mut self, name: &'static str, version: u32) -> bool
+        where T: Component + Serialize + DeserializeOwned
+    {
+        let registered = self.register::<T>();
+        self.serde_registry.insert::<T>(name, version);
+        registered
+    }
+    #[cfg(feature = "serde")]
+    #[inline]
+    pub fn register_migration<T, F>(&mut self, from_version: u32, migration: F) -> bool
+        where T: Component,
+              F: Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync + 'static
+    {
+        self.serde_registry.insert_migration::<T, F>(from_version, migration)
+    }
+    #[cfg(feature = "serde")]
+    #[inline]
+    pub fn serde_registry(&self) -> &SerdeRegistry {
+        &self.serde_registry
+    }
+    #[inline]
+    pub fn is_registered<T: Component>(&self) -> bool {
+        self.component_managers.contains_key(&TypeId::of::<T>())
     }
     #[inline]
     pub fn unregister<T: Component>(&mut self) {
-        self.component_managers.remove(&TypeId::of::<T>());
+        let type_id = TypeId::of::<T>();
+
+        self.component_managers.remove(&type_id);
+        if let Some(requirements) = self.requirements.remove(&type_id) {
+            for requirement in requirements.iter() {
+                if let Some(required_by) = self.required_by.get_mut(&requirement.type_id()) {
+                    required_by.retain(|required_by| required_by != &type_id);
+                }
+            }
+        }
     }
 
     #[inline]
@@ -45,42 +161,297 @@ impl Components {
     }
     #[inline]
     pub fn component_manager<T: Component>(&self) -> &RwLock<WrappedComponentManager<T>> {
-        unsafe {
-            self.component_managers
-                .get(&TypeId::of::<T>())
-                .expect("unregistered component use, make sure to register components.")
-                .downcast_ref_unchecked::<RwLock<WrappedComponentManager<T>>>()
+        match self.try_component_manager::<T>() {
+            Ok(component_manager) => component_manager,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    #[inline]
+    pub fn try_component_manager<T: Component>(&self) -> Result<&RwLock<WrappedComponentManager<T>>, Error> {
+        match self.get_component_manager::<T>() {
+            Some(component_manager) => Ok(component_manager),
+            None => Err(Error::UnregisteredComponent(any::type_name::<T>())),
+        }
+    }
+    #[inline]
+    pub fn get_component_manager<T: Component>(&self) -> Option<&RwLock<WrappedComponentManager<T>>> {
+        match self.component_managers.get(&TypeId::of::<T>()) {
+            Some(component_manager) => {
+                if Any::type_id(&**component_manager) == TypeId::of::<RwLock<WrappedComponentManager<T>>>() {
+                    Some(unsafe {
+                        component_manager.downcast_ref_unchecked::<RwLock<WrappedComponentManager<T>>>()
+                    })
+                } else {
+                    None
+                }
+            },
+            None => None,
         }
     }
 
     #[inline]
     pub fn insert<T: Component>(&mut self, entity: Entity, component: T) {
-        match self.component_manager::<T>().write() {
-            Ok(mut component_manager) => component_manager.insert(entity, component),
-            Err(..) => panic!("unregistered component inserted, make sure to register components."),
+        self.register::<T>();
+
+        if let Err(error) = self.try_insert(entity, component) {
+            panic!("{}", error);
         }
     }
+    // takes &self so it can run under a shared Components lock, which means it can not
+    // register T, only insert and EntityManager::try_insert_component auto-register
     #[inline]
-    pub fn remove<T: Component>(&mut self, entity: &Entity) -> Option<T> {
-        match self.component_manager::<T>().write() {
-            Ok(mut component_manager) => component_manager.remove(entity),
+    pub fn try_insert<T: Component>(&self, entity: Entity, component: T) -> Result<(), Error> {
+        let component_manager = self.try_component_manager::<T>()?;
+
+        // missing requirements go in first so on_insert hooks of T can see them
+        if let Some(requirements) = self.requirements.get(&TypeId::of::<T>()) {
+            for requirement in requirements.iter() {
+                if !self.contains_type(&requirement.type_id(), &entity)? {
+                    requirement.insert_default(self, entity)?;
+                }
+            }
+        }
+
+        match component_manager.write() {
+            Ok(mut component_manager) => {
+                component_manager.insert(entity, component);
+                Ok(())
+            },
+            Err(..) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    #[inline]
+    fn contains_type(&self, type_id: &TypeId, entity: &Entity) -> Result<bool, Error> {
+        match self.component_managers.get(type_id) {
+            Some(component_manager) => component_manager.contains(entity),
+            None => Ok(false),
+        }
+    }
+    // errors if the entity has the type and another component on the entity requires it
+    fn check_required(&self, type_id: &TypeId, name: &'static str, entity: &Entity) -> Result<(), Error> {
+        if !self.contains_type(type_id, entity)? {
+            return Ok(());
+        }
+        let required_by = match self.required_by.get(type_id) {
+            Some(required_by) => required_by,
+            None => return Ok(()),
+        };
+
+        for required_by in required_by.iter() {
+            if self.contains_type(required_by, entity)? {
+                let required_by = match self.component_managers.get(required_by) {
+                    Some(component_manager) => component_manager.name(),
+                    None => "unknown",
+                };
+                return Err(Error::RequiredComponent(name, required_by));
+            }
+        }
+        Ok(())
+    }
+    #[inline]
+    pub fn count<T: Component>(&self) -> Result<usize, Error> {
+        match self.try_component_manager::<T>()?.read() {
+            Ok(component_manager) => Ok(component_manager.len()),
+            Err(..) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    // the number of components stored for every registered type
+    pub fn counts(&self) -> Result<Vector<(&'static str, usize)>, Error> {
+        let mut counts = Vector::with_capacity(self.component_managers.len());
+
+        for (_, component_manager) in self.component_managers.iter() {
+            counts.push((component_manager.name(), component_manager.count()?));
+        }
+
+        Ok(counts)
+    }
+    // removes every component of the entity
+    pub fn remove_entity(&self, entity: &Entity) -> Result<(), Error> {
+        for (_, component_manager) in self.component_managers.iter() {
+            component_manager.remove_entity(entity)?;
+        }
+        Ok(())
+    }
+    pub fn snapshot(&self) -> Result<HashMap<TypeId, Box<Any + Send + Sync>>, Error> {
+        let mut snapshot = HashMap::new();
+
+        for (type_id, component_manager) in self.component_managers.iter() {
+            if let Some(component_snapshot) = component_manager.snapshot()? {
+                snapshot.insert(*type_id, component_snapshot);
+            }
+        }
+
+        Ok(snapshot)
+    }
+    // entities are the restored entities, storages that can not be snapshot are pruned to them
+    pub fn restore(&self, snapshot: &HashMap<TypeId, Box<Any + Send + Sync>>, entities: &Entities) -> Result<(), Error> {
+        for (type_id, component_manager) in self.component_managers.iter() {
+            match snapshot.get(type_id) {
+                Some(component_snapshot) => {
+                    component_manager.restore(Some(&**component_snapshot), entities)?;
+                },
+                None => {
+                    component_manager.restore(None, entities)?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // panics if another component on the entity requires T, try_remove returns the error instead
+    #[inline]
+    pub fn remove<T: Component>(&self, entity: &Entity) -> Option<T> {
+        match self.try_remove(entity) {
+            Ok(component) => component,
+            Err(error @ Error::RequiredComponent(..)) => panic!("{}", error),
             Err(..) => None,
         }
     }
+    #[inline]
+    pub fn try_remove<T: Component>(&self, entity: &Entity) -> Result<Option<T>, Error> {
+        self.check_required(&TypeId::of::<T>(), any::type_name::<T>(), entity)?;
+
+        match self.try_component_manager::<T>()?.write() {
+            Ok(mut component_manager) => Ok(component_manager.remove(entity)),
+            Err(..) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+}
+
+
+// panics when a requirement leads back to a component already on the path,
+// inserting any of them would otherwise recurse forever, runs before anything is registered
+fn check_requirement_cycle(path: &mut Vector<(TypeId, &'static str)>, requirements: &Vector<Requirement>) {
+    for requirement in requirements.iter() {
+        if path.iter().any(|&(type_id, _)| type_id == requirement.type_id()) {
+            let name = path[path.len() - 1].1;
+            panic!("component {} requires {}, which forms a requirement cycle", name, requirement.name());
+        }
+
+        path.push((requirement.type_id(), requirement.name()));
+        check_requirement_cycle(path, &requirement.required());
+        path.pop();
+    }
+}
+
+#[inline]
+fn clone_component<T: Clone>(component: &T) -> T {
+    component.clone()
+}
+#[inline]
+fn map_entities<T: MapEntities>(component: &mut T, entity_map: &EntityMap) {
+    component.map_entities(entity_map);
 }
 
 
 pub trait ComponentManagerLock: Any + Send + Sync {
-    fn replace(&mut self);
+    fn name(&self) -> &'static str;
+    fn count(&self) -> Result<usize, Error>;
+    fn contains(&self, &Entity) -> Result<bool, Error>;
+    fn remove_entity(&self, &Entity) -> Result<bool, Error>;
+    fn replace(&mut self) -> Result<(), Error>;
+    fn clear(&self) -> Result<(), Error>;
+    fn map_entities(&self, &EntityMap) -> Result<(), Error>;
+    fn clone_entities(&self, &EntityMap) -> Result<(), Error>;
+    fn prefab_component(&self, &Entity) -> Result<Option<Box<PrefabComponent>>, Error>;
+    fn snapshot(&self) -> Result<Option<Box<Any + Send + Sync>>, Error>;
+    // restores a snapshot taken from the same component type, None clears cloneable storage,
+    // storage without a clone hook keeps only the components of the restored entities
+    fn restore(&self, snapshot: Option<&(Any + Send + Sync)>, &Entities) -> Result<(), Error>;
 }
 
 impl_any!(ComponentManagerLock);
 
 impl<T: Component> ComponentManagerLock for RwLock<WrappedComponentManager<T>> {
-    fn replace(&mut self) {
+    #[inline]
+    fn name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+    fn count(&self) -> Result<usize, Error> {
+        match self.read() {
+            Ok(components) => Ok(components.len()),
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn contains(&self, entity: &Entity) -> Result<bool, Error> {
+        match self.read() {
+            Ok(components) => Ok(components.contains(entity)),
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn remove_entity(&self, entity: &Entity) -> Result<bool, Error> {
         match self.write() {
-            Ok(ref mut components) => components.replace(),
-            Err(_) => panic!("failed to replace components"),
+            Ok(mut components) => Ok(components.remove(entity).is_some()),
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn replace(&mut self) -> Result<(), Error> {
+        match self.write() {
+            Ok(ref mut components) => {
+                components.replace();
+                Ok(())
+            },
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn clear(&self) -> Result<(), Error> {
+        match self.write() {
+            Ok(mut components) => {
+                components.clear();
+                Ok(())
+            },
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn map_entities(&self, entity_map: &EntityMap) -> Result<(), Error> {
+        match self.write() {
+            Ok(mut components) => {
+                components.map_entities(entity_map);
+                Ok(())
+            },
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn clone_entities(&self, entity_map: &EntityMap) -> Result<(), Error> {
+        match self.write() {
+            Ok(mut components) => {
+                components.clone_entities(entity_map);
+                Ok(())
+            },
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn prefab_component(&self, entity: &Entity) -> Result<Option<Box<PrefabComponent>>, Error> {
+        match self.read() {
+            Ok(components) => Ok(match (components.clone_fn(), components.clone_component(entity)) {
+                (Some(clone), Some(component)) => Some(Box::new(ClonedComponent::new(component, clone)) as Box<PrefabComponent>),
+                _ => None,
+            }),
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn snapshot(&self) -> Result<Option<Box<Any + Send + Sync>>, Error> {
+        match self.read() {
+            Ok(components) => Ok(match components.snapshot() {
+                Some(snapshot) => Some(Box::new(snapshot)),
+                None => None,
+            }),
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
+        }
+    }
+    fn restore(&self, snapshot: Option<&(Any + Send + Sync)>, entities: &Entities) -> Result<(), Error> {
+        match self.write() {
+            Ok(mut components) => {
+                let restored = match snapshot.and_then(|snapshot| snapshot.downcast_ref::<Vector<(Entity, T)>>()) {
+                    Some(snapshot) => components.restore(snapshot),
+                    None => components.restore(&[]),
+                };
+                if !restored {
+                    components.retain_entities(entities);
+                }
+                Ok(())
+            },
+            Err(_) => Err(Error::Poisoned(any::type_name::<T>())),
         }
     }
 }
@@ -108,9 +479,46 @@ mod test {
         let entity = entities.create();
 
         components.register::<SomeComponent>();
-        components.insert(entity.clone(), SomeComponent);
+        components.insert(entity, SomeComponent);
 
         let component_manager = components.component_manager::<SomeComponent>().read().unwrap();
         assert_eq!(component_manager.get(&entity).unwrap().as_ref(), &SomeComponent);
     }
+
+    #[test]
+    fn test_components_unregistered() {
+        let mut components = Components::new();
+        let mut entities = Entities::new();
+        let entity = entities.create();
+
+        assert!(components.get_component_manager::<SomeComponent>().is_none());
+        match components.try_remove::<SomeComponent>(&entity) {
+            Err(Error::UnregisteredComponent(_)) => (),
+            _ => panic!("expected unregistered component error"),
+        }
+
+        components.insert(entity, SomeComponent);
+        assert!(components.is_registered::<SomeComponent>());
+        assert!(!components.register::<SomeComponent>());
+
+        let component_manager = components.component_manager::<SomeComponent>().read().unwrap();
+        assert!(component_manager.contains(&entity));
+    }
+
+    #[test]
+    fn test_components_count() {
+        let mut components = Components::new();
+        let mut entities = Entities::new();
+
+        assert!(components.count::<SomeComponent>().is_err());
+
+        components.insert(entities.create(), SomeComponent);
+        components.insert(entities.create(), SomeComponent);
+
+        assert_eq!(components.count::<SomeComponent>().unwrap(), 2);
+
+        let counts = components.counts().unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[0], (any::type_name::<SomeComponent>(), 2));
+    }
 }
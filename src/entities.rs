@@ -1,11 +1,30 @@
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+
 use collection_traits::*;
+use hash_map::HashMap;
 use hash_set::HashSet;
+use vector::Vector;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeSeq;
 
 use super::entity::Entity;
+use super::entity_allocator::{EntityAllocator, RandomAllocator};
+use super::entity_map::EntityMap;
 
 
+#[derive(Clone)]
 pub struct Entities {
+    allocator: Arc<EntityAllocator>,
     entities: HashSet<Entity>,
+    names: HashMap<Entity, String>,
+    named: HashMap<String, Vector<Entity>>,
+    groups: HashMap<String, HashSet<Entity>>,
+    memberships: HashMap<Entity, Vector<String>>,
 }
 
 unsafe impl Send for Entities {}
@@ -14,26 +33,318 @@ unsafe impl Sync for Entities {}
 impl Entities {
     #[inline]
     pub fn new() -> Self {
+        Self::new_with_allocator(Arc::new(RandomAllocator))
+    }
+    #[inline]
+    pub fn new_with_allocator(allocator: Arc<EntityAllocator>) -> Self {
         Entities {
+            allocator: allocator,
             entities: HashSet::new(),
+            names: HashMap::new(),
+            named: HashMap::new(),
+            groups: HashMap::new(),
+            memberships: HashMap::new(),
         }
     }
     #[inline]
     pub fn create(&mut self) -> Entity {
-        let entity = Entity::new();
+        let entity = self.allocate();
         self.entities.insert(entity);
         entity
     }
+    // hands out an id that is not alive without making it alive
+    #[inline]
+    pub fn allocate(&self) -> Entity {
+        allocate_unused(&*self.allocator, &self.entities)
+    }
+    #[inline]
+    pub fn create_many(&mut self, count: usize) -> Vector<Entity> {
+        let mut entities = self.allocator.allocate_many(count);
+
+        self.entities.reserve(count);
+        for entity in entities.iter_mut() {
+            if !self.entities.insert(*entity) {
+                *entity = allocate_unused(&*self.allocator, &self.entities);
+                self.entities.insert(*entity);
+            }
+        }
+
+        entities
+    }
+    // entities inserted from outside are reported to the allocator so it does not hand them out
+    #[inline]
+    pub fn insert(&mut self, entity: Entity) -> bool {
+        self.allocator.observe(entity);
+        self.entities.insert(entity)
+    }
+    // takes the state of the other entities, keeping this allocator
+    #[inline]
+    pub fn restore(&mut self, entities: &Entities) {
+        let allocator = self.allocator.clone();
+        *self = entities.clone();
+        self.allocator = allocator;
+    }
+    #[inline]
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.named.clear();
+        self.groups.clear();
+        self.memberships.clear();
+        self.entities.clear();
+    }
     #[inline]
     pub fn remove(&mut self, entity: &Entity) -> bool {
+        self.remove_name(entity);
+        self.remove_from_groups(entity);
         self.entities.remove(entity)
     }
+    // returns the number of entities that were alive
+    #[inline]
+    pub fn remove_many(&mut self, entities: &[Entity]) -> usize {
+        entities.iter().filter(|entity| self.remove(entity)).count()
+    }
+
+    // names and groups are runtime only, snapshots keep them but saved and binary files do not
+    // only alive entities can be named, returns the previous name
+    pub fn set_name<S: Into<String>>(&mut self, entity: &Entity, name: S) -> Option<String> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let name = name.into();
+        let previous = self.remove_name(entity);
+
+        if let Some(entities) = self.named.get_mut(&name) {
+            entities.push(*entity);
+        } else {
+            let mut entities = Vector::new();
+            entities.push(*entity);
+            self.named.insert(name.clone(), entities);
+        }
+        self.names.insert(*entity, name);
+
+        previous
+    }
+    pub fn remove_name(&mut self, entity: &Entity) -> Option<String> {
+        match self.names.remove(entity) {
+            Some(name) => {
+                let empty = match self.named.get_mut(&name) {
+                    Some(entities) => {
+                        entities.retain(|named| named != entity);
+                        entities.is_empty()
+                    },
+                    None => false,
+                };
+                if empty {
+                    self.named.remove(&name);
+                }
+
+                Some(name)
+            },
+            None => None,
+        }
+    }
+
+    // only alive entities can join groups, returns false if already a member
+    pub fn add_to_group<S: Into<String>>(&mut self, entity: &Entity, group: S) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        let group = group.into();
+        let added = if let Some(entities) = self.groups.get_mut(&group) {
+            entities.insert(*entity)
+        } else {
+            let mut entities = HashSet::new();
+            entities.insert(*entity);
+            self.groups.insert(group.clone(), entities);
+            true
+        };
+
+        if added {
+            if let Some(groups) = self.memberships.get_mut(entity) {
+                groups.push(group);
+                return true;
+            }
+            let mut groups = Vector::new();
+            groups.push(group);
+            self.memberships.insert(*entity, groups);
+        }
+
+        added
+    }
+    pub fn remove_from_group(&mut self, entity: &Entity, group: &str) -> bool {
+        let removed = match self.groups.get_mut(group) {
+            Some(entities) => entities.remove(entity),
+            None => false,
+        };
+
+        if removed {
+            if self.groups.get(group).map_or(false, |entities| entities.is_empty()) {
+                self.groups.remove(group);
+            }
+            if let Some(groups) = self.memberships.get_mut(entity) {
+                groups.retain(|name| name != group);
+            }
+            if self.memberships.get(entity).map_or(false, |groups| groups.is_empty()) {
+                self.memberships.remove(entity);
+            }
+        }
+
+        removed
+    }
+    fn remove_from_groups(&mut self, entity: &Entity) {
+        if let Some(groups) = self.memberships.remove(entity) {
+            for group in groups.iter() {
+                let empty = match self.groups.get_mut(group) {
+                    Some(entities) => {
+                        entities.remove(entity);
+                        entities.is_empty()
+                    },
+                    None => false,
+                };
+                if empty {
+                    self.groups.remove(group);
+                }
+            }
+        }
+    }
+    #[inline]
+    pub fn is_in_group(&self, entity: &Entity, group: &str) -> bool {
+        self.groups.get(group).map_or(false, |entities| entities.contains(entity))
+    }
+    #[inline]
+    pub fn group(&self, group: &str) -> Vector<Entity> {
+        match self.groups.get(group) {
+            Some(entities) => entities.iter().cloned().collect(),
+            None => Vector::new(),
+        }
+    }
+    #[inline]
+    pub fn groups(&self, entity: &Entity) -> Vector<String> {
+        match self.memberships.get(entity) {
+            Some(groups) => groups.clone(),
+            None => Vector::new(),
+        }
+    }
+
+    #[inline]
+    pub fn name(&self, entity: &Entity) -> Option<&str> {
+        self.names.get(entity).map(|name| name.as_str())
+    }
+    // debug formats the entity with its name
+    #[inline]
+    pub fn debug_entity(&self, entity: &Entity) -> NamedEntity {
+        NamedEntity {
+            entity: *entity,
+            name: self.names.get(entity).cloned(),
+        }
+    }
+    // the first entity given the name
+    #[inline]
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.named.get(name).and_then(|entities| entities.first().cloned())
+    }
+    #[inline]
+    pub fn allocator(&self) -> &Arc<EntityAllocator> {
+        &self.allocator
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.iter()
+    }
     #[inline]
     pub fn is_alive(&self, entity: &Entity) -> bool {
-        self.entities.contains(&entity)
+        self.entities.contains(entity)
+    }
+
+    // replaces every entity with a newly created one
+    pub fn remap(&mut self) -> EntityMap {
+        let mut entity_map = EntityMap::new();
+        let mut entities = HashSet::new();
+
+        for entity in self.entities.iter() {
+            let new_entity = allocate_unused(&*self.allocator, &entities);
+            entities.insert(new_entity);
+            entity_map.insert(*entity, new_entity);
+        }
+
+        self.entities = entities;
+        self.named.clear();
+
+        for (entity, name) in mem::replace(&mut self.names, HashMap::new()) {
+            self.set_name(&entity_map.map(entity), name);
+        }
+
+        self.groups.clear();
+        for (entity, groups) in mem::replace(&mut self.memberships, HashMap::new()) {
+            for group in groups {
+                self.add_to_group(&entity_map.map(entity), group);
+            }
+        }
+
+        entity_map
+    }
+}
+
+// the allocator can hand out ids that are alive after a load, those are skipped
+#[inline]
+fn allocate_unused(allocator: &EntityAllocator, entities: &HashSet<Entity>) -> Entity {
+    loop {
+        let entity = allocator.allocate();
+
+        if !entities.contains(&entity) {
+            return entity;
+        }
+    }
+}
+
+
+pub struct NamedEntity {
+    entity: Entity,
+    name: Option<String>,
+}
+
+impl NamedEntity {
+    #[inline]
+    pub fn entity(&self) -> Entity { self.entity }
+    #[inline]
+    pub fn name(&self) -> Option<&str> { self.name.as_deref() }
+}
+
+impl fmt::Debug for NamedEntity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(ref name) => f.debug_struct("Entity").field("id", &self.entity.id()).field("name", name).finish(),
+            None => f.debug_struct("Entity").field("id", &self.entity.id()).finish(),
+        }
+    }
+}
+
+
+#[cfg(feature = "serde")]
+impl Serialize for Entities {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entities: Vec<&Entity> = self.entities.iter().collect();
+        entities.sort_by_key(|entity| entity.id());
+
+        let mut seq = serializer.serialize_seq(Some(entities.len()))?;
+        for entity in entities {
+            seq.serialize_element(entity)?;
+        }
+        seq.end()
     }
 }
 
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -47,4 +358,57 @@ mod test {
         assert!(entities.remove(&entity));
         assert!(!entities.is_alive(&entity));
     }
+
+    #[test]
+    fn test_entities_many() {
+        let mut entities = Entities::new();
+        let created = entities.create_many(1024);
+
+        assert_eq!(created.len(), 1024);
+        assert!(created.iter().all(|entity| entities.is_alive(entity)));
+        assert_eq!(entities.len(), 1024);
+        assert_eq!(entities.iter().count(), 1024);
+        assert_eq!(entities.remove_many(&created[..512]), 512);
+        assert_eq!(entities.remove_many(&created[..512]), 0);
+        assert!(!entities.is_alive(&created[0]));
+        assert!(entities.is_alive(&created[512]));
+    }
+
+    #[test]
+    fn test_entities_names() {
+        let mut entities = Entities::new();
+        let entity = entities.create();
+
+        assert_eq!(entities.set_name(&entity, "player"), None);
+        assert_eq!(entities.find_by_name("player"), Some(entity));
+        assert!(format!("{:?}", entities.debug_entity(&entity)).contains("player"));
+
+        assert_eq!(entities.set_name(&entity, "hero"), Some("player".to_owned()));
+        assert_eq!(entities.find_by_name("player"), None);
+
+        entities.remove(&entity);
+        assert_eq!(entities.find_by_name("hero"), None);
+        assert!(!format!("{:?}", entities.debug_entity(&entity)).contains("hero"));
+    }
+
+    #[test]
+    fn test_entities_groups() {
+        let mut entities = Entities::new();
+        let enemy = entities.create();
+        let pickup = entities.create();
+
+        assert!(entities.add_to_group(&enemy, "enemies"));
+        assert!(!entities.add_to_group(&enemy, "enemies"));
+        assert!(entities.add_to_group(&pickup, "pickups"));
+
+        assert!(entities.is_in_group(&enemy, "enemies"));
+        assert_eq!(entities.group("enemies").len(), 1);
+
+        assert!(entities.remove_from_group(&pickup, "pickups"));
+        assert!(entities.group("pickups").is_empty());
+
+        entities.remove(&enemy);
+        assert!(!entities.is_in_group(&enemy, "enemies"));
+        assert!(entities.group("enemies").is_empty());
+    }
 }
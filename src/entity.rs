@@ -1,6 +1,8 @@
 use std::hash::{Hash, Hasher};
 
+use collection_traits::*;
 use hash_map::DefaultHasher;
+use vector::Vector;
 
 use uuid::Uuid;
 
@@ -12,8 +14,26 @@ fn next_id() -> u64 {
     hasher.finish()
 }
 
+// hashes one uuid with each index instead of generating a uuid per id
+#[inline]
+fn next_ids(count: usize) -> Vector<u64> {
+    let uuid = Uuid::new_v4();
+    let mut ids = Vector::with_capacity(count);
+
+    for index in 0..count {
+        let mut hasher = DefaultHasher::default();
+        uuid.hash(&mut hasher);
+        index.hash(&mut hasher);
+        ids.push(hasher.finish());
+    }
+
+    ids
+}
+
 
 #[derive(Debug, Hash, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Entity {
     id: u64,
 }
@@ -29,6 +49,21 @@ impl Entity {
             id: next_id(),
         }
     }
+
+    #[inline]
+    pub fn from_id(id: u64) -> Self {
+        Entity {
+            id: id,
+        }
+    }
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    #[inline]
+    pub fn new_many(count: usize) -> Vector<Self> {
+        next_ids(count).into_iter().map(|id| Entity { id: id }).collect()
+    }
 }
 
 
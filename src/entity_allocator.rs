@@ -0,0 +1,147 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use collection_traits::*;
+use vector::Vector;
+
+use super::entity::Entity;
+
+
+pub trait EntityAllocator: Send + Sync {
+    fn allocate(&self) -> Entity;
+
+    #[inline]
+    fn allocate_many(&self, count: usize) -> Vector<Entity> {
+        (0..count).map(|_| self.allocate()).collect()
+    }
+
+    // called for entities inserted from outside the allocator, like loaded or restored ones
+    #[inline]
+    fn observe(&self, _: Entity) {}
+
+    // the position of the allocator so snapshots can rewind it
+    #[inline]
+    fn state(&self) -> u64 { 0 }
+    #[inline]
+    fn set_state(&self, _: u64) {}
+}
+
+
+// uuid based ids, different every run
+pub struct RandomAllocator;
+
+impl EntityAllocator for RandomAllocator {
+    #[inline]
+    fn allocate(&self) -> Entity {
+        Entity::new()
+    }
+    #[inline]
+    fn allocate_many(&self, count: usize) -> Vector<Entity> {
+        Entity::new_many(count)
+    }
+}
+
+
+pub struct SequentialAllocator {
+    next: AtomicU64,
+}
+
+impl SequentialAllocator {
+    #[inline]
+    pub fn new() -> Self {
+        Self::starting_at(0)
+    }
+    #[inline]
+    pub fn starting_at(id: u64) -> Self {
+        SequentialAllocator {
+            next: AtomicU64::new(id),
+        }
+    }
+}
+
+impl EntityAllocator for SequentialAllocator {
+    #[inline]
+    fn allocate(&self) -> Entity {
+        Entity::from_id(self.next.fetch_add(1, Ordering::Relaxed))
+    }
+    #[inline]
+    fn observe(&self, entity: Entity) {
+        self.next.fetch_max(entity.id().wrapping_add(1), Ordering::Relaxed);
+    }
+    #[inline]
+    fn state(&self) -> u64 {
+        self.next.load(Ordering::Relaxed)
+    }
+    #[inline]
+    fn set_state(&self, state: u64) {
+        self.next.store(state, Ordering::Relaxed);
+    }
+}
+
+
+// splitmix64 over a counter, ids look random but repeat for the same seed,
+// observed ids can not be mapped back to the counter so Entities skips ids already alive
+pub struct SeededAllocator {
+    seed: u64,
+    next: AtomicU64,
+}
+
+impl SeededAllocator {
+    #[inline]
+    pub fn new(seed: u64) -> Self {
+        SeededAllocator {
+            seed: seed,
+            next: AtomicU64::new(0),
+        }
+    }
+    #[inline]
+    pub fn seed(&self) -> u64 { self.seed }
+}
+
+impl EntityAllocator for SeededAllocator {
+    #[inline]
+    fn allocate(&self) -> Entity {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        Entity::from_id(splitmix64(self.seed.wrapping_add(index.wrapping_mul(0x9E37_79B9_7F4A_7C15))))
+    }
+    #[inline]
+    fn state(&self) -> u64 {
+        self.next.load(Ordering::Relaxed)
+    }
+    #[inline]
+    fn set_state(&self, state: u64) {
+        self.next.store(state, Ordering::Relaxed);
+    }
+}
+
+#[inline]
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn test_seeded_allocator() {
+        let a = SeededAllocator::new(42);
+        let b = SeededAllocator::new(42);
+        let c = SeededAllocator::new(7);
+
+        let a = a.allocate_many(64);
+        let b = b.allocate_many(64);
+        let c = c.allocate_many(64);
+
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| a == b));
+        assert!(a.iter().zip(c.iter()).all(|(a, c)| a != c));
+
+        let sequential = SequentialAllocator::starting_at(10);
+        assert_eq!(sequential.allocate().id(), 10);
+        assert_eq!(sequential.allocate().id(), 11);
+    }
+}
@@ -0,0 +1,135 @@
+use collection_traits::*;
+use vector::Vector;
+
+use super::component::Component;
+use super::components::Components;
+use super::entity::Entity;
+use super::entity_manager::EntityManager;
+use super::error::Error;
+
+
+pub struct EntityBuilder<'a> {
+    entity_manager: &'a EntityManager,
+    entity: Entity,
+    components: Vector<Box<PendingComponent>>,
+}
+
+impl<'a> EntityBuilder<'a> {
+    #[inline]
+    pub fn new(entity_manager: &'a EntityManager) -> Self {
+        EntityBuilder {
+            entity_manager: entity_manager,
+            entity: entity_manager.entities().allocate(),
+            components: Vector::new(),
+        }
+    }
+
+    // the entity is not alive until build is called
+    #[inline]
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    #[inline]
+    pub fn with<T: Component>(mut self, component: T) -> Self {
+        self.components.push(Box::new(Pending(component)));
+        self
+    }
+
+    #[inline]
+    pub fn build(self) -> Entity {
+        match self.try_build() {
+            Ok(entity) => entity,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    // fails without touching components if the entity became alive since the builder was made
+    pub fn try_build(self) -> Result<Entity, Error> {
+        let EntityBuilder { entity_manager, entity, components: pending } = self;
+
+        if entity_manager.try_entities()?.is_alive(&entity) {
+            return Err(Error::DuplicateEntity(entity));
+        }
+
+        let registered = {
+            let components = entity_manager.try_components()?;
+            pending.iter().all(|component| component.is_registered(&components))
+        };
+        if !registered {
+            let mut components = entity_manager.try_components_mut()?;
+
+            for component in pending.iter() {
+                component.register(&mut components);
+            }
+        }
+
+        {
+            let components = entity_manager.try_components()?;
+
+            for component in pending {
+                component.insert_into(&components, entity)?;
+            }
+        }
+
+        if entity_manager.try_entities_mut()?.insert(entity) {
+            Ok(entity)
+        } else {
+            Err(Error::DuplicateEntity(entity))
+        }
+    }
+}
+
+
+trait PendingComponent: Send + Sync {
+    fn is_registered(&self, &Components) -> bool;
+    fn register(&self, &mut Components);
+    fn insert_into(self: Box<Self>, &Components, Entity) -> Result<(), Error>;
+}
+
+struct Pending<T: Component>(T);
+
+impl<T: Component> PendingComponent for Pending<T> {
+    #[inline]
+    fn is_registered(&self, components: &Components) -> bool {
+        components.is_registered::<T>()
+    }
+    #[inline]
+    fn register(&self, components: &mut Components) {
+        components.register::<T>();
+    }
+    #[inline]
+    fn insert_into(self: Box<Self>, components: &Components, entity: Entity) -> Result<(), Error> {
+        components.try_insert(entity, self.0)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use component_manager::HashMapComponentManager;
+
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Health(u32);
+
+    impl Component for Health {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+
+    #[test]
+    fn test_entity_builder() {
+        let entity_manager = EntityManager::new();
+        let builder = entity_manager.build_entity().with(Health(10));
+        let pending = builder.entity();
+
+        assert!(!entity_manager.is_entity_alive(&pending));
+
+        let entity = builder.build();
+        assert_eq!(entity, pending);
+        assert!(entity_manager.is_entity_alive(&entity));
+        assert_eq!(entity_manager.query::<Health>().entities().len(), 1);
+        assert_eq!(entity_manager.remove_component::<Health>(&entity), Some(Health(10)));
+    }
+}
@@ -1,91 +1,550 @@
 use std::sync::{Arc, RwLock, RwLockWriteGuard, RwLockReadGuard};
 
 use collection_traits::*;
+use thread_pool::ThreadPool;
+use vector::Vector;
 
+use super::bundle::Bundle;
 use super::component::Component;
 use super::components::Components;
-use super::entities::Entities;
+use super::entities::{Entities, NamedEntity};
 use super::entity::Entity;
+use super::entity_allocator::{EntityAllocator, RandomAllocator};
+use super::entity_builder::EntityBuilder;
+use super::entity_map::{EntityMap, MapEntities};
+use super::error::Error;
+use super::hierarchy;
+use super::lifecycle_hooks::LifecycleHooks;
+use super::prefab::Prefab;
+use super::query::Query;
+use super::reserved_entities::ReservedEntities;
+use super::snapshot::Snapshot;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
 
 
 #[derive(Clone)]
 pub struct EntityManager {
     components: Arc<RwLock<Components>>,
     entities: Arc<RwLock<Entities>>,
+    reserved: Arc<ReservedEntities>,
+    allocator: Arc<EntityAllocator>,
+    thread_pool: Option<Arc<ThreadPool>>,
 }
 
 impl EntityManager {
     #[inline]
     pub fn new() -> Self {
+        Self::new_with_allocator(Arc::new(RandomAllocator))
+    }
+    // the allocator is shared by Entities, reserve_entity and build_entity
+    #[inline]
+    pub fn new_with_allocator(allocator: Arc<EntityAllocator>) -> Self {
         EntityManager {
             components: Arc::new(RwLock::new(Components::new())),
-            entities: Arc::new(RwLock::new(Entities::new())),
+            entities: Arc::new(RwLock::new(Entities::new_with_allocator(allocator.clone()))),
+            reserved: Arc::new(ReservedEntities::new()),
+            allocator: allocator,
+            thread_pool: None,
         }
     }
 
     #[inline]
-    pub fn components(&self) -> RwLockReadGuard<Components> {
+    pub fn allocator(&self) -> &Arc<EntityAllocator> {
+        &self.allocator
+    }
+
+    // the pool used by Query::par_for_each, clones made before this is set do not share it
+    #[inline]
+    pub fn set_thread_pool(&mut self, thread_pool: Arc<ThreadPool>) {
+        self.thread_pool = Some(thread_pool);
+    }
+    #[inline]
+    pub fn thread_pool(&self) -> Option<&Arc<ThreadPool>> {
+        self.thread_pool.as_ref()
+    }
+
+    #[inline]
+    pub fn components(&self) -> RwLockReadGuard<'_, Components> {
         self.components.read().expect("failed to acquire lock on components")
     }
     #[inline]
-    pub fn components_mut(&self) -> RwLockWriteGuard<Components> {
+    pub fn try_components(&self) -> Result<RwLockReadGuard<'_, Components>, Error> {
+        self.components.read().map_err(|_| Error::Poisoned("components"))
+    }
+    #[inline]
+    pub fn components_mut(&self) -> RwLockWriteGuard<'_, Components> {
         self.components.write().expect("failed to acquire lock on components")
     }
+    #[inline]
+    pub fn try_components_mut(&self) -> Result<RwLockWriteGuard<'_, Components>, Error> {
+        self.components.write().map_err(|_| Error::Poisoned("components"))
+    }
 
     #[inline]
-    pub fn entities(&self) -> RwLockReadGuard<Entities> {
+    pub fn entities(&self) -> RwLockReadGuard<'_, Entities> {
         self.entities.read().expect("failed to acquire lock on entities")
     }
     #[inline]
-    pub fn entities_mut(&self) -> RwLockWriteGuard<Entities> {
+    pub fn try_entities(&self) -> Result<RwLockReadGuard<'_, Entities>, Error> {
+        self.entities.read().map_err(|_| Error::Poisoned("entities"))
+    }
+    #[inline]
+    pub fn entities_mut(&self) -> RwLockWriteGuard<'_, Entities> {
         self.entities.write().expect("failed to acquire lock on entities")
     }
+    #[inline]
+    pub fn try_entities_mut(&self) -> Result<RwLockWriteGuard<'_, Entities>, Error> {
+        self.entities.write().map_err(|_| Error::Poisoned("entities"))
+    }
 
     #[inline]
     pub fn create_entity(&self) -> Entity {
         self.entities_mut().create()
     }
+    // hands out an id under a shared entities lock, the entity becomes alive on the next update
+    #[inline]
+    pub fn reserve_entity(&self) -> Entity {
+        let entity = self.entities().allocate();
+        self.reserved.push(entity);
+        entity
+    }
+    #[inline]
+    pub fn create_many(&self, count: usize) -> Vector<Entity> {
+        self.entities_mut().create_many(count)
+    }
+    // also unlinks the entity from its parent and children, which write locks the
+    // Parent and Children storages, so do not call it while iterating either of them
     #[inline]
     pub fn remove_entity(&self, entity: &Entity) -> bool {
+        if let Err(error) = hierarchy::unlink(&self.components(), entity) {
+            panic!("{}", error);
+        }
         self.entities_mut().remove(entity)
     }
     #[inline]
+    pub fn remove_many(&self, entities: &[Entity]) -> usize {
+        {
+            let components = self.components();
+
+            for entity in entities {
+                if let Err(error) = hierarchy::unlink(&components, entity) {
+                    panic!("{}", error);
+                }
+            }
+        }
+        self.entities_mut().remove_many(entities)
+    }
+    #[inline]
     pub fn is_entity_alive(&self, entity: &Entity) -> bool {
         self.entities().is_alive(entity)
     }
+    #[inline]
+    pub fn set_entity_name<S: Into<String>>(&self, entity: &Entity, name: S) -> Option<String> {
+        self.entities_mut().set_name(entity, name)
+    }
+    #[inline]
+    pub fn remove_entity_name(&self, entity: &Entity) -> Option<String> {
+        self.entities_mut().remove_name(entity)
+    }
+    #[inline]
+    pub fn entity_name(&self, entity: &Entity) -> Option<String> {
+        self.entities().name(entity).map(|name| name.to_owned())
+    }
+    #[inline]
+    pub fn debug_entity(&self, entity: &Entity) -> NamedEntity {
+        self.entities().debug_entity(entity)
+    }
+    #[inline]
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.entities().find_by_name(name)
+    }
+    #[inline]
+    pub fn add_to_group<S: Into<String>>(&self, entity: &Entity, group: S) -> bool {
+        self.entities_mut().add_to_group(entity, group)
+    }
+    #[inline]
+    pub fn remove_from_group(&self, entity: &Entity, group: &str) -> bool {
+        self.entities_mut().remove_from_group(entity, group)
+    }
+    #[inline]
+    pub fn is_in_group(&self, entity: &Entity, group: &str) -> bool {
+        self.entities().is_in_group(entity, group)
+    }
+    #[inline]
+    pub fn group(&self, group: &str) -> Vector<Entity> {
+        self.entities().group(group)
+    }
+    #[inline]
+    pub fn entity_count(&self) -> usize {
+        self.entities().len()
+    }
+    #[inline]
+    pub fn entity_list(&self) -> Vector<Entity> {
+        self.entities().iter().cloned().collect()
+    }
+    #[inline]
+    pub fn component_count<T: Component>(&self) -> Result<usize, Error> {
+        self.try_components()?.count::<T>()
+    }
+    #[inline]
+    pub fn component_counts(&self) -> Result<Vector<(&'static str, usize)>, Error> {
+        self.try_components()?.counts()
+    }
 
     #[inline]
-    pub fn register_component<T: Component>(&self) {
+    pub fn register_component<T: Component>(&self) -> bool {
         self.components_mut().register::<T>()
     }
     #[inline]
+    pub fn try_register_component<T: Component>(&self) -> Result<bool, Error> {
+        Ok(self.try_components_mut()?.register::<T>())
+    }
+    #[inline]
+    pub fn register_component_with<T: Component>(&self, hooks: LifecycleHooks<T>) -> bool {
+        self.components_mut().register_with::<T>(hooks)
+    }
+    #[inline]
+    pub fn register_clone_component<T: Component + Clone>(&self) -> bool {
+        self.components_mut().register_clone::<T>()
+    }
+    #[cfg(feature = "serde")]
+    #[inline]
+    pub fn register_serde_component<T>(&self, name: &'static str) -> bool
+        where T: Component + Serialize + DeserializeOwned
+    {
+        self.components_mut().register_serde::<T>(name)
+    }
+    #[cfg(feature = "serde")]
+    #[inline]
+    pub fn register_serde_component_versioned<T>(&self, name: &'static str, version: u32) -> bool
+        where T: Component + Serialize + DeserializeOwned
+    {
+        self.components_mut().register_serde_versioned::<T>(name, version)
+    }
+    #[cfg(feature = "serde")]
+    #[inline]
+    pub fn register_component_migration<T, F>(&self, from_version: u32, migration: F) -> bool
+        where T: Component,
+              F: Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync + 'static
+    {
+        self.components_mut().register_migration::<T, F>(from_version, migration)
+    }
+    #[inline]
+    pub fn register_map_entities_component<T: Component + MapEntities>(&self) -> bool {
+        self.components_mut().register_map_entities::<T>()
+    }
+    #[inline]
     pub fn unregister_component<T: Component>(&self) {
         self.components_mut().unregister::<T>()
     }
 
     #[inline]
     pub fn insert_component<T: Component>(&self, entity: Entity, component: T) {
-        self.components_mut().insert::<T>(entity, component)
+        if let Err(error) = self.try_insert_component(entity, component) {
+            panic!("{}", error);
+        }
     }
     #[inline]
+    pub fn try_insert_component<T: Component>(&self, entity: Entity, component: T) -> Result<(), Error> {
+        if !self.try_components()?.is_registered::<T>() {
+            self.try_components_mut()?.register::<T>();
+        }
+        self.try_components()?.try_insert::<T>(entity, component)
+    }
+    // panics if another component on the entity requires T
+    #[inline]
     pub fn remove_component<T: Component>(&self, entity: &Entity) -> Option<T> {
-        self.components_mut().remove::<T>(entity)
+        self.components().remove::<T>(entity)
+    }
+    #[inline]
+    pub fn try_remove_component<T: Component>(&self, entity: &Entity) -> Result<Option<T>, Error> {
+        self.try_components()?.try_remove::<T>(entity)
+    }
+
+    pub fn snapshot(&self) -> Result<Snapshot, Error> {
+        let components = self.try_components()?;
+        let entities = self.try_entities()?;
+        Ok(Snapshot::new(entities.clone(), components.snapshot()?, self.allocator.state()))
+    }
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<(), Error> {
+        let components = self.try_components_mut()?;
+        let mut entities = self.try_entities_mut()?;
+
+        components.restore(snapshot.components(), snapshot.entities())?;
+        entities.restore(snapshot.entities());
+        self.allocator.set_state(snapshot.allocator_state());
+
+        Ok(())
+    }
+
+    pub fn remap_entities(&self) -> Result<EntityMap, Error> {
+        let components = self.try_components_mut()?;
+        let entity_map = self.try_entities_mut()?.remap();
+
+        for (_, component_manager) in components.component_managers().iter() {
+            component_manager.map_entities(&entity_map)?;
+        }
+
+        Ok(entity_map)
+    }
+
+    // creates a copy of every entity with its cloneable components,
+    // entity references inside components are remapped to the copies
+    pub fn clone_entities(&self, entities: &[Entity]) -> Result<EntityMap, Error> {
+        let components = self.try_components_mut()?;
+        let mut entity_map = EntityMap::new();
+
+        {
+            let mut alive = self.try_entities_mut()?;
+
+            for entity in entities {
+                if alive.is_alive(entity) {
+                    entity_map.insert(*entity, alive.create());
+                }
+            }
+        }
+
+        for (_, component_manager) in components.component_managers().iter() {
+            component_manager.clone_entities(&entity_map)?;
+        }
+
+        Ok(entity_map)
+    }
+
+    #[inline]
+    pub fn build_entity(&self) -> EntityBuilder<'_> {
+        EntityBuilder::new(self)
+    }
+    #[inline]
+    pub fn spawn<B: Bundle>(&self, bundle: B) -> Entity {
+        match self.try_spawn(bundle) {
+            Ok(entity) => entity,
+            Err(error) => panic!("{}", error),
+        }
     }
+    pub fn try_spawn<B: Bundle>(&self, bundle: B) -> Result<Entity, Error> {
+        {
+            let components = self.try_components()?;
+
+            if B::is_registered(&components) {
+                let entity = self.try_entities_mut()?.create();
+                bundle.insert_into(&components, entity)?;
+                return Ok(entity);
+            }
+        }
+
+        let mut components = self.try_components_mut()?;
+        B::register(&mut components);
 
+        let entity = self.try_entities_mut()?.create();
+        bundle.insert_into(&components, entity)?;
+        Ok(entity)
+    }
     #[inline]
-    fn replace(&self) -> &Self {
-        let mut components = self.components_mut();
+    pub fn insert_bundle<B: Bundle>(&self, entity: Entity, bundle: B) -> Result<(), Error> {
+        {
+            let components = self.try_components()?;
+
+            if B::is_registered(&components) {
+                return bundle.insert_into(&components, entity);
+            }
+        }
+
+        let mut components = self.try_components_mut()?;
+        B::register(&mut components);
+        bundle.insert_into(&components, entity)
+    }
+
+    #[inline]
+    pub fn spawn_prefab(&self, prefab: &Prefab) -> Entity {
+        match self.try_spawn_prefab(prefab) {
+            Ok(entity) => entity,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    pub fn try_spawn_prefab(&self, prefab: &Prefab) -> Result<Entity, Error> {
+        if !prefab.is_registered(&*self.try_components()?) {
+            prefab.register(&mut *self.try_components_mut()?);
+        }
+
+        let entity = self.try_entities_mut()?.create();
+        prefab.insert_into(&*self.try_components()?, entity)?;
+        Ok(entity)
+    }
+    // captures the entity's cloneable components into a prefab,
+    // components registered without a clone fn are listed in the prefab's skipped names
+    pub fn prefab(&self, entity: &Entity) -> Result<Prefab, Error> {
+        let components = self.try_components()?;
+        let mut prefab = Prefab::new();
+
+        for (_, component_manager) in components.component_managers().iter() {
+            if let Some(component) = component_manager.prefab_component(entity)? {
+                prefab.insert_boxed(component);
+            } else if component_manager.contains(entity)? {
+                prefab.insert_skipped(component_manager.name());
+            }
+        }
+
+        Ok(prefab)
+    }
+
+    #[inline]
+    pub fn query<T: Component>(&self) -> Query<T> {
+        Query::new(self.clone())
+    }
+
+    #[inline]
+    fn replace(&self) -> Result<&Self, Error> {
+        let mut components = self.try_components_mut()?;
         let mut component_managers = components.component_managers_mut();
 
         for (_, component_manager) in component_managers.iter_mut() {
-            component_manager.replace();
+            component_manager.replace()?;
         }
 
-        self
+        Ok(self)
     }
     #[inline]
     pub fn update(&self) -> &Self {
-        self.replace();
+        if let Err(error) = self.try_update() {
+            panic!("{}", error);
+        }
         self
     }
+    #[inline]
+    fn materialize_reserved(&self) -> Result<&Self, Error> {
+        if !self.reserved.is_empty() {
+            let mut entities = self.try_entities_mut()?;
+
+            for entity in self.reserved.drain() {
+                entities.insert(entity);
+            }
+        }
+        Ok(self)
+    }
+    #[inline]
+    pub fn try_update(&self) -> Result<&Self, Error> {
+        self.replace()?;
+        self.materialize_reserved()?;
+        Ok(self)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use component_manager::HashMapComponentManager;
+    use entity_allocator::{SequentialAllocator, SeededAllocator};
+
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct SomeComponent;
+
+    impl Component for SomeComponent {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+
+    #[test]
+    fn test_entity_manager_shared_components_lock() {
+        let entity_manager = EntityManager::new();
+        let entity = entity_manager.create_entity();
+
+        entity_manager.register_component::<SomeComponent>();
+
+        let _components = entity_manager.components();
+        entity_manager.insert_component(entity, SomeComponent);
+        assert_eq!(entity_manager.remove_component::<SomeComponent>(&entity), Some(SomeComponent));
+    }
+
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Target(Entity);
+
+    impl Component for Target {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+    impl MapEntities for Target {
+        fn map_entities(&mut self, entity_map: &EntityMap) {
+            self.0.map_entities(entity_map);
+        }
+    }
+
+
+    #[test]
+    fn test_entity_manager_clone_entities() {
+        let entity_manager = EntityManager::new();
+        entity_manager.register_clone_component::<Target>();
+        entity_manager.register_map_entities_component::<Target>();
+
+        let a = entity_manager.create_entity();
+        let b = entity_manager.create_entity();
+        entity_manager.insert_component(a, Target(b));
+        entity_manager.insert_component(b, Target(a));
+
+        let entity_map = entity_manager.clone_entities(&[a, b]).unwrap();
+        let new_a = entity_map.get(&a).unwrap();
+        let new_b = entity_map.get(&b).unwrap();
+
+        assert_eq!(entity_manager.remove_component::<Target>(&new_a), Some(Target(new_b)));
+        assert_eq!(entity_manager.remove_component::<Target>(&new_b), Some(Target(new_a)));
+        assert_eq!(entity_manager.remove_component::<Target>(&a), Some(Target(b)));
+    }
+
+    #[test]
+    fn test_entity_manager_reserve_entity() {
+        let entity_manager = EntityManager::new();
+        let entity = entity_manager.reserve_entity();
+
+        assert!(!entity_manager.is_entity_alive(&entity));
+        entity_manager.update();
+        assert!(entity_manager.is_entity_alive(&entity));
+    }
+
+    #[test]
+    fn test_entity_manager_allocator_load() {
+        let entity_manager = EntityManager::new_with_allocator(Arc::new(SequentialAllocator::new()));
+
+        // loading inserts ids the allocator did not hand out
+        for id in 0..4u64 {
+            entity_manager.entities_mut().insert(Entity::from_id(id));
+        }
+        assert_eq!(entity_manager.create_entity().id(), 4u64);
+
+        let seeded = EntityManager::new_with_allocator(Arc::new(SeededAllocator::new(1u64)));
+        let loaded = SeededAllocator::new(1u64).allocate();
+
+        seeded.entities_mut().insert(loaded);
+        assert_ne!(seeded.create_entity(), loaded);
+        assert_eq!(seeded.entity_count(), 2usize);
+
+        // builders and reservations skip loaded ids too
+        let seeded = EntityManager::new_with_allocator(Arc::new(SeededAllocator::new(2u64)));
+        let loaded = SeededAllocator::new(2u64).allocate_many(2);
+        for entity in loaded.iter() {
+            seeded.entities_mut().insert(*entity);
+        }
+        let built = seeded.build_entity().build();
+        let reserved = seeded.reserve_entity();
+        assert!(!loaded.contains(&built) && !loaded.contains(&reserved));
+        assert_ne!(built, reserved);
+    }
+
+    #[test]
+    fn test_entity_manager_allocator_restore() {
+        let entity_manager = EntityManager::new_with_allocator(Arc::new(SeededAllocator::new(7u64)));
+        entity_manager.create_entity();
+
+        let snapshot = entity_manager.snapshot().unwrap();
+        let first = entity_manager.create_entity();
+
+        entity_manager.restore(&snapshot).unwrap();
+        assert_eq!(entity_manager.create_entity(), first);
+    }
 }
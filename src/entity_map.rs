@@ -0,0 +1,80 @@
+use collection_traits::*;
+use hash_map::HashMap;
+use vector::Vector;
+
+use super::entity::Entity;
+
+
+#[derive(Clone)]
+pub struct EntityMap {
+    map: HashMap<Entity, Entity>,
+}
+
+unsafe impl Send for EntityMap {}
+unsafe impl Sync for EntityMap {}
+
+impl EntityMap {
+    #[inline]
+    pub fn new() -> Self {
+        EntityMap {
+            map: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.len() == 0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, from: Entity, to: Entity) -> Option<Entity> {
+        self.map.insert(from, to)
+    }
+    #[inline]
+    pub fn get(&self, entity: &Entity) -> Option<Entity> {
+        self.map.get(entity).cloned()
+    }
+    // entities missing from the map are left as they are
+    #[inline]
+    pub fn map(&self, entity: Entity) -> Entity {
+        self.get(&entity).unwrap_or(entity)
+    }
+    #[inline]
+    pub fn pairs(&self) -> Vector<(Entity, Entity)> {
+        self.map.iter().map(|(from, to)| (*from, *to)).collect()
+    }
+}
+
+
+pub trait MapEntities {
+    fn map_entities(&mut self, &EntityMap);
+}
+
+impl MapEntities for Entity {
+    #[inline]
+    fn map_entities(&mut self, entity_map: &EntityMap) {
+        *self = entity_map.map(*self);
+    }
+}
+
+impl<T: MapEntities> MapEntities for Option<T> {
+    #[inline]
+    fn map_entities(&mut self, entity_map: &EntityMap) {
+        if let Some(ref mut value) = *self {
+            value.map_entities(entity_map);
+        }
+    }
+}
+
+impl<T: MapEntities> MapEntities for Vector<T> {
+    #[inline]
+    fn map_entities(&mut self, entity_map: &EntityMap) {
+        for value in self.iter_mut() {
+            value.map_entities(entity_map);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use vector::Vector;
+
+use super::entity::Entity;
+use super::process_panic::ProcessPanic;
+
+
+#[derive(Debug)]
+pub enum Error {
+    UnregisteredComponent(&'static str),
+    Poisoned(&'static str),
+    ProcessPanicked(Vector<ProcessPanic>),
+    Io(io::Error),
+    Serialization(String),
+    HierarchyCycle(Entity, Entity),
+    DeadEntity(Entity),
+    DuplicateEntity(Entity),
+    RequiredComponent(&'static str, &'static str),
+}
+
+impl From<io::Error> for Error {
+    #[inline]
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::UnregisteredComponent(name) => write!(f, "unregistered component {} used, make sure to register components", name),
+            &Error::Poisoned(name) => write!(f, "lock on {} is poisoned", name),
+            &Error::ProcessPanicked(ref panics) => {
+                write!(f, "{} process(es) panicked", panics.len())?;
+                for panic in panics.iter() {
+                    write!(f, ", {}", panic)?;
+                }
+                Ok(())
+            },
+            &Error::Io(ref error) => write!(f, "{}", error),
+            &Error::Serialization(ref message) => write!(f, "serialization failed: {}", message),
+            &Error::RequiredComponent(name, required_by) => write!(f, "component {} is required by {}", name, required_by),
+            &Error::HierarchyCycle(child, parent) => write!(f, "setting the parent of {:?} to {:?} would create a cycle", child, parent),
+            &Error::DeadEntity(entity) => write!(f, "entity {:?} is not alive", entity),
+            &Error::DuplicateEntity(entity) => write!(f, "entity {:?} is already alive", entity),
+        }
+    }
+}
+
+impl error::Error for Error {}
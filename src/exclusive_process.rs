@@ -0,0 +1,19 @@
+use std::any::{self, Any};
+
+use super::components::Components;
+use super::entities::Entities;
+
+
+// runs with both the components and the entities write locked, it may change them
+// freely but must not replace the Entities value, that would drop its allocator
+pub trait ExclusiveProcess: Any + Send + Sync {
+    fn run(&mut self, &mut Components, &mut Entities);
+    #[inline]
+    fn priority(&self) -> usize {
+        0usize
+    }
+    #[inline]
+    fn name(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+}
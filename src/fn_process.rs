@@ -0,0 +1,33 @@
+use super::entity_manager::EntityManager;
+use super::process::Process;
+
+
+pub struct FnProcess<F> {
+    priority: usize,
+    f: F,
+}
+
+impl<F> FnProcess<F>
+    where F: FnMut(&EntityManager) + Send + Sync + 'static
+{
+    #[inline]
+    pub fn new(priority: usize, f: F) -> Self {
+        FnProcess {
+            priority: priority,
+            f: f,
+        }
+    }
+}
+
+impl<F> Process for FnProcess<F>
+    where F: FnMut(&EntityManager) + Send + Sync + 'static
+{
+    #[inline]
+    fn run(&mut self, entity_manager: &EntityManager) {
+        (self.f)(entity_manager)
+    }
+    #[inline]
+    fn priority(&self) -> usize {
+        self.priority
+    }
+}
@@ -0,0 +1,293 @@
+use std::any;
+
+use collection_traits::*;
+use vector::Vector;
+
+use super::component::Component;
+use super::component_manager::{ComponentManager, HashMapComponentManager};
+use super::components::Components;
+use super::entity::Entity;
+use super::entity_manager::EntityManager;
+use super::entity_map::{EntityMap, MapEntities};
+use super::error::Error;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+
+// set through EntityManager::set_parent so both sides of the link stay consistent
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Parent(Entity);
+
+impl Parent {
+    #[inline]
+    pub fn entity(&self) -> Entity { self.0 }
+}
+
+impl Component for Parent {
+    type ComponentManager = HashMapComponentManager<Self>;
+}
+
+impl MapEntities for Parent {
+    #[inline]
+    fn map_entities(&mut self, entity_map: &EntityMap) {
+        self.0.map_entities(entity_map);
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Children(Vector<Entity>);
+
+impl Children {
+    #[inline]
+    pub fn entities(&self) -> &Vector<Entity> { &self.0 }
+}
+
+impl Component for Children {
+    type ComponentManager = HashMapComponentManager<Self>;
+}
+
+impl MapEntities for Children {
+    #[inline]
+    fn map_entities(&mut self, entity_map: &EntityMap) {
+        self.0.map_entities(entity_map);
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Children {
+    #[inline]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Children {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let children: Vec<Entity> = Deserialize::deserialize(deserializer)?;
+        Ok(Children(children.into_iter().collect()))
+    }
+}
+
+
+// hierarchy edits take the components write lock so they can not interleave
+impl EntityManager {
+    pub fn set_parent(&self, child: Entity, parent: Entity) -> Result<(), Error> {
+        let mut components = self.try_components_mut()?;
+        register(&mut components);
+
+        {
+            let entities = self.try_entities()?;
+
+            for entity in &[child, parent] {
+                if !entities.is_alive(entity) {
+                    return Err(Error::DeadEntity(*entity));
+                }
+            }
+        }
+
+        let mut ancestor = Some(parent);
+        while let Some(entity) = ancestor {
+            if entity == child {
+                return Err(Error::HierarchyCycle(child, parent));
+            }
+            ancestor = parent_of(&components, &entity)?;
+        }
+
+        detach(&components, &child)?;
+        components.try_insert(child, Parent(parent))?;
+
+        let mut children = match components.try_remove::<Children>(&parent)? {
+            Some(children) => children,
+            None => Children(Vector::new()),
+        };
+        children.0.push(child);
+        components.try_insert(parent, children)
+    }
+    #[inline]
+    pub fn remove_parent(&self, child: &Entity) -> Result<Option<Entity>, Error> {
+        let mut components = self.try_components_mut()?;
+        register(&mut components);
+        detach(&components, child)
+    }
+    #[inline]
+    pub fn parent(&self, entity: &Entity) -> Result<Option<Entity>, Error> {
+        parent_of(&*self.try_components()?, entity)
+    }
+    #[inline]
+    pub fn children(&self, entity: &Entity) -> Result<Vector<Entity>, Error> {
+        children_of(&*self.try_components()?, entity)
+    }
+
+    // removes the entity, its descendants and all of their components,
+    // returns the number of entities removed
+    pub fn despawn_recursive(&self, entity: &Entity) -> Result<usize, Error> {
+        let mut components = self.try_components_mut()?;
+        register(&mut components);
+        detach(&components, entity)?;
+
+        let mut stack = Vector::new();
+        let mut despawned = Vector::new();
+
+        stack.push(*entity);
+        while let Some(entity) = stack.pop() {
+            if let Some(children) = components.try_remove::<Children>(&entity)? {
+                for child in children.0 {
+                    stack.push(child);
+                }
+            }
+            despawned.push(entity);
+        }
+
+        for entity in despawned.iter() {
+            components.remove_entity(entity)?;
+        }
+
+        Ok(self.try_entities_mut()?.remove_many(&despawned))
+    }
+}
+
+
+#[inline]
+fn register(components: &mut Components) {
+    components.register_clone::<Parent>();
+    components.register_map_entities::<Parent>();
+    components.register_clone::<Children>();
+    components.register_map_entities::<Children>();
+}
+
+#[inline]
+fn parent_of(components: &Components, entity: &Entity) -> Result<Option<Entity>, Error> {
+    match components.get_component_manager::<Parent>() {
+        Some(component_manager) => match component_manager.read() {
+            Ok(component_manager) => Ok(component_manager.get(entity).map(|parent| parent.as_ref().0)),
+            Err(..) => Err(Error::Poisoned(any::type_name::<Parent>())),
+        },
+        None => Ok(None),
+    }
+}
+
+#[inline]
+fn children_of(components: &Components, entity: &Entity) -> Result<Vector<Entity>, Error> {
+    match components.get_component_manager::<Children>() {
+        Some(component_manager) => match component_manager.read() {
+            Ok(component_manager) => Ok(match component_manager.get(entity) {
+                Some(children) => children.as_ref().0.clone(),
+                None => Vector::new(),
+            }),
+            Err(..) => Err(Error::Poisoned(any::type_name::<Children>())),
+        },
+        None => Ok(Vector::new()),
+    }
+}
+
+// removes the child from its parent, keeping both components consistent
+fn detach(components: &Components, child: &Entity) -> Result<Option<Entity>, Error> {
+    match components.try_remove::<Parent>(child)? {
+        Some(Parent(parent)) => {
+            remove_child(components, &parent, child)?;
+            Ok(Some(parent))
+        },
+        None => Ok(None),
+    }
+}
+
+// edits the parent's children under one storage lock, so removals running
+// under a shared components lock can not lose each other's edits
+fn remove_child(components: &Components, parent: &Entity, child: &Entity) -> Result<(), Error> {
+    if let Some(component_manager) = components.get_component_manager::<Children>() {
+        let mut component_manager = match component_manager.write() {
+            Ok(component_manager) => component_manager,
+            Err(..) => return Err(Error::Poisoned(any::type_name::<Children>())),
+        };
+
+        if let Some(Children(mut children)) = component_manager.remove(parent) {
+            children.retain(|entity| entity != child);
+
+            if !children.is_empty() {
+                component_manager.insert(*parent, Children(children));
+            }
+        }
+    }
+    Ok(())
+}
+
+// removes the entity from the hierarchy, its children are left without a parent,
+// used when entities are removed so no Parent or Children points at a dead entity
+pub fn unlink(components: &Components, entity: &Entity) -> Result<(), Error> {
+    if !components.is_registered::<Parent>() || !components.is_registered::<Children>() {
+        return Ok(());
+    }
+
+    detach(components, entity)?;
+
+    if let Some(Children(children)) = components.try_remove::<Children>(entity)? {
+        for child in children.iter() {
+            components.try_remove::<Parent>(child)?;
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+
+    #[test]
+    fn test_hierarchy() {
+        let entity_manager = EntityManager::new();
+        let root = entity_manager.create_entity();
+        let child = entity_manager.create_entity();
+        let grandchild = entity_manager.create_entity();
+
+        entity_manager.set_parent(child, root).unwrap();
+        entity_manager.set_parent(grandchild, child).unwrap();
+
+        assert_eq!(entity_manager.parent(&grandchild).unwrap(), Some(child));
+        assert_eq!(entity_manager.children(&root).unwrap().len(), 1);
+
+        match entity_manager.set_parent(root, grandchild) {
+            Err(Error::HierarchyCycle(..)) => (),
+            _ => panic!("expected hierarchy cycle error"),
+        }
+
+        assert_eq!(entity_manager.despawn_recursive(&child).unwrap(), 2);
+        assert!(entity_manager.is_entity_alive(&root));
+        assert!(!entity_manager.is_entity_alive(&grandchild));
+        assert!(entity_manager.children(&root).unwrap().is_empty());
+        assert_eq!(entity_manager.parent(&grandchild).unwrap(), None);
+    }
+
+    #[test]
+    fn test_hierarchy_remove_entity() {
+        let entity_manager = EntityManager::new();
+        let root = entity_manager.create_entity();
+        let child = entity_manager.create_entity();
+        let grandchild = entity_manager.create_entity();
+        let other = entity_manager.create_entity();
+
+        entity_manager.set_parent(child, root).unwrap();
+        entity_manager.set_parent(grandchild, child).unwrap();
+        entity_manager.set_parent(other, root).unwrap();
+
+        assert!(entity_manager.remove_entity(&child));
+        assert_eq!(entity_manager.children(&root).unwrap().len(), 1);
+        assert_eq!(entity_manager.parent(&grandchild).unwrap(), None);
+
+        assert_eq!(entity_manager.remove_many(&[root]), 1);
+        assert_eq!(entity_manager.parent(&other).unwrap(), None);
+        assert!(entity_manager.children(&root).unwrap().is_empty());
+
+        match entity_manager.set_parent(other, root) {
+            Err(Error::DeadEntity(entity)) => assert_eq!(entity, root),
+            _ => panic!("expected dead entity error"),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-#![feature(get_type_id)]
+#![allow(bare_trait_objects, anonymous_parameters)]
+#![allow(clippy::redundant_field_names, clippy::new_without_default, clippy::match_ref_pats, clippy::needless_borrowed_reference)]
 
 
 extern crate atomic;
@@ -11,30 +12,77 @@ extern crate waiter;
 extern crate thread_pool;
 extern crate vector;
 
+#[cfg(feature = "serde")] #[macro_use] extern crate serde;
+#[cfg(feature = "serde")] extern crate erased_serde;
+#[cfg(feature = "serde")] extern crate serde_json;
+#[cfg(feature = "serde")] extern crate bincode;
 
+
+mod error;
+
+#[macro_use]
+mod bundle;
 mod component_manager;
 mod component;
 mod components;
 
 mod entities;
 mod entity_manager;
+mod entity_map;
 mod entity;
+mod entity_builder;
+mod lifecycle_hooks;
+mod entity_allocator;
+mod hierarchy;
+mod prefab;
+mod query;
+mod requirement;
+mod reserved_entities;
 
+mod exclusive_process;
+mod fn_process;
+mod process_panic;
 mod process;
 mod processes;
 
 mod scene;
+mod snapshot;
+
+#[cfg(feature = "serde")]
+mod binary;
+#[cfg(feature = "serde")]
+mod serialize;
 
 
+pub use self::error::Error;
+
+pub use self::bundle::Bundle;
 pub use self::component_manager::*;
 pub use self::component::Component;
 pub use self::components::Components;
 
-pub use self::entities::Entities;
+pub use self::entities::{Entities, NamedEntity};
 pub use self::entity_manager::EntityManager;
+pub use self::entity_map::{EntityMap, MapEntities};
 pub use self::entity::Entity;
+pub use self::entity_builder::EntityBuilder;
+pub use self::lifecycle_hooks::{Hook, LifecycleHooks};
+pub use self::entity_allocator::{EntityAllocator, RandomAllocator, SequentialAllocator, SeededAllocator};
+pub use self::hierarchy::{Parent, Children};
+pub use self::prefab::{Prefab, PrefabComponent, ClonedComponent};
+pub use self::query::Query;
+pub use self::requirement::Requirement;
 
+pub use self::exclusive_process::ExclusiveProcess;
+pub use self::fn_process::FnProcess;
 pub use self::process::Process;
-pub use self::processes::Processes;
+pub use self::process_panic::{PanicPolicy, ProcessPanic};
+pub use self::processes::{Processes, ProcessEntry, ProcessHandle, ProcessLock, ExclusiveProcessLock};
 
 pub use self::scene::Scene;
+pub use self::snapshot::Snapshot;
+
+#[cfg(feature = "serde")]
+pub use self::binary::FORMAT_VERSION;
+#[cfg(feature = "serde")]
+pub use self::serialize::{DeserializedState, Migration, SerdeComponent, SerdeRegistry};
@@ -0,0 +1,127 @@
+use super::component::Component;
+use super::entity::Entity;
+
+
+pub type Hook<T> = Fn(&Entity, &T) + Send + Sync;
+
+
+// hooks run while the component type's storage is write locked, so a hook must not
+// insert, remove or query components of its own type or it will deadlock
+pub struct LifecycleHooks<T: Component> {
+    on_insert: Option<Box<Hook<T>>>,
+    on_replace: Option<Box<Hook<T>>>,
+    on_remove: Option<Box<Hook<T>>>,
+}
+
+impl<T: Component> LifecycleHooks<T> {
+    #[inline]
+    pub fn new() -> Self {
+        LifecycleHooks {
+            on_insert: None,
+            on_replace: None,
+            on_remove: None,
+        }
+    }
+
+    // called with the new component after it is inserted
+    #[inline]
+    pub fn on_insert<F: Fn(&Entity, &T) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.on_insert = Some(Box::new(f));
+        self
+    }
+    // called with the old component when an insert overwrites it, before on_insert
+    #[inline]
+    pub fn on_replace<F: Fn(&Entity, &T) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.on_replace = Some(Box::new(f));
+        self
+    }
+    // called with the component after it is removed
+    #[inline]
+    pub fn on_remove<F: Fn(&Entity, &T) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.on_remove = Some(Box::new(f));
+        self
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.on_insert.is_none() && self.on_replace.is_none() && self.on_remove.is_none()
+    }
+
+    #[inline]
+    pub fn inserted(&self, entity: &Entity, component: &T) {
+        if let Some(ref on_insert) = self.on_insert {
+            on_insert(entity, component);
+        }
+    }
+    #[inline]
+    pub fn replaced(&self, entity: &Entity, component: &T) {
+        if let Some(ref on_replace) = self.on_replace {
+            on_replace(entity, component);
+        }
+    }
+    #[inline]
+    pub fn removed(&self, entity: &Entity, component: &T) {
+        if let Some(ref on_remove) = self.on_remove {
+            on_remove(entity, component);
+        }
+    }
+}
+
+impl<T: Component> Default for LifecycleHooks<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use components::Components;
+    use entities::Entities;
+    use component_manager::HashMapComponentManager;
+
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Body(u32);
+
+    impl Component for Body {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+
+    #[test]
+    fn test_lifecycle_hooks() {
+        let inserted = Arc::new(AtomicUsize::new(0));
+        let replaced = Arc::new(AtomicUsize::new(0));
+        let removed = Arc::new(AtomicUsize::new(0));
+
+        let mut components = Components::new();
+        let mut entities = Entities::new();
+        let entity = entities.create();
+
+        {
+            let inserted = inserted.clone();
+            let replaced = replaced.clone();
+            let removed = removed.clone();
+
+            components.register_with(LifecycleHooks::<Body>::new()
+                .on_insert(move |_, body| { inserted.fetch_add(body.0 as usize, Ordering::SeqCst); })
+                .on_replace(move |_, body| { replaced.fetch_add(body.0 as usize, Ordering::SeqCst); })
+                .on_remove(move |_, body| { removed.fetch_add(body.0 as usize, Ordering::SeqCst); }));
+        }
+
+        components.insert(entity, Body(1));
+        components.insert(entity, Body(2));
+        assert_eq!(components.remove::<Body>(&entity), Some(Body(2)));
+
+        assert_eq!(inserted.load(Ordering::SeqCst), 3);
+        assert_eq!(replaced.load(Ordering::SeqCst), 1);
+        assert_eq!(removed.load(Ordering::SeqCst), 2);
+    }
+}
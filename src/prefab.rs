@@ -0,0 +1,164 @@
+use collection_traits::*;
+use vector::Vector;
+
+use super::component::Component;
+use super::components::Components;
+use super::entity::Entity;
+use super::error::Error;
+
+
+pub struct Prefab {
+    components: Vector<Box<PrefabComponent>>,
+    skipped: Vector<&'static str>,
+}
+
+unsafe impl Send for Prefab {}
+unsafe impl Sync for Prefab {}
+
+impl Prefab {
+    #[inline]
+    pub fn new() -> Self {
+        Prefab {
+            components: Vector::new(),
+            skipped: Vector::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.components.len() == 0
+    }
+
+    // names of components the source entity had that could not be cloned into this prefab
+    #[inline]
+    pub fn skipped(&self) -> &Vector<&'static str> {
+        &self.skipped
+    }
+    #[inline]
+    pub fn insert_skipped(&mut self, name: &'static str) -> &mut Self {
+        self.skipped.push(name);
+        self
+    }
+
+    #[inline]
+    pub fn with<T: Component + Clone>(mut self, component: T) -> Self {
+        self.insert(component);
+        self
+    }
+    #[inline]
+    pub fn insert<T: Component + Clone>(&mut self, component: T) -> &mut Self {
+        self.insert_boxed(Box::new(ClonedComponent::new(component, T::clone)))
+    }
+    #[inline]
+    pub fn insert_boxed(&mut self, component: Box<PrefabComponent>) -> &mut Self {
+        self.components.push(component);
+        self
+    }
+
+    #[inline]
+    pub fn is_registered(&self, components: &Components) -> bool {
+        self.components.iter().all(|component| component.is_registered(components))
+    }
+    #[inline]
+    pub fn register(&self, components: &mut Components) {
+        for component in self.components.iter() {
+            component.register(components);
+        }
+    }
+    // inserts a copy of every component, all components must already be registered
+    pub fn insert_into(&self, components: &Components, entity: Entity) -> Result<(), Error> {
+        for component in self.components.iter() {
+            component.insert_into(components, entity)?;
+        }
+        Ok(())
+    }
+}
+
+
+pub trait PrefabComponent: Send + Sync {
+    fn is_registered(&self, &Components) -> bool;
+    fn register(&self, &mut Components);
+    fn insert_into(&self, &Components, Entity) -> Result<(), Error>;
+}
+
+
+pub struct ClonedComponent<T: Component> {
+    component: T,
+    clone: fn(&T) -> T,
+}
+
+impl<T: Component> ClonedComponent<T> {
+    #[inline]
+    pub fn new(component: T, clone: fn(&T) -> T) -> Self {
+        ClonedComponent {
+            component: component,
+            clone: clone,
+        }
+    }
+}
+
+impl<T: Component> PrefabComponent for ClonedComponent<T> {
+    #[inline]
+    fn is_registered(&self, components: &Components) -> bool {
+        components.is_registered::<T>()
+    }
+    #[inline]
+    fn register(&self, components: &mut Components) {
+        components.register::<T>();
+    }
+    #[inline]
+    fn insert_into(&self, components: &Components, entity: Entity) -> Result<(), Error> {
+        components.try_insert(entity, (self.clone)(&self.component))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use component_manager::HashMapComponentManager;
+    use entity_manager::EntityManager;
+
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Health(u32);
+
+    impl Component for Health {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Enemy;
+
+    impl Component for Enemy {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+
+    #[test]
+    fn test_prefab() {
+        let entity_manager = EntityManager::new();
+        let prefab = Prefab::new().with(Health(10)).with(Enemy);
+
+        let a = entity_manager.spawn_prefab(&prefab);
+        let b = entity_manager.spawn_prefab(&prefab);
+        assert_ne!(a, b);
+
+        entity_manager.register_clone_component::<Health>();
+        let copy = entity_manager.prefab(&b).unwrap();
+        assert_eq!(copy.len(), 1);
+        assert_eq!(copy.skipped().len(), 1);
+        assert!(copy.skipped()[0].ends_with("Enemy"));
+        let c = entity_manager.spawn_prefab(&copy);
+
+        assert_eq!(entity_manager.remove_component::<Health>(&a), Some(Health(10)));
+        assert_eq!(entity_manager.remove_component::<Enemy>(&a), Some(Enemy));
+        assert_eq!(entity_manager.remove_component::<Health>(&b), Some(Health(10)));
+        assert_eq!(entity_manager.remove_component::<Health>(&c), Some(Health(10)));
+        assert_eq!(entity_manager.remove_component::<Enemy>(&c), None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::any::Any;
+use std::any::{self, Any};
 
 use super::entity_manager::EntityManager;
 
@@ -9,4 +9,8 @@ pub trait Process: Any + Send + Sync {
     fn priority(&self) -> usize {
         0usize
     }
+    #[inline]
+    fn name(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
 }
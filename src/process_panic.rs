@@ -0,0 +1,63 @@
+use std::any::Any;
+use std::fmt;
+
+use super::processes::ProcessHandle;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// report the panic and skip committing the frame
+    Abort,
+    /// report the panic and stop running the process
+    Disable,
+    /// report the panic and run the process again next update
+    Continue,
+}
+
+impl Default for PanicPolicy {
+    #[inline]
+    fn default() -> Self {
+        PanicPolicy::Abort
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessPanic {
+    handle: ProcessHandle,
+    name: &'static str,
+    message: String,
+}
+
+impl ProcessPanic {
+    #[inline]
+    pub fn new(handle: ProcessHandle, name: &'static str, payload: &(Any + Send)) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&'static str>() {
+            (*message).to_owned()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<Any>".to_owned()
+        };
+
+        ProcessPanic {
+            handle: handle,
+            name: name,
+            message: message,
+        }
+    }
+
+    #[inline]
+    pub fn handle(&self) -> ProcessHandle { self.handle }
+    #[inline]
+    pub fn name(&self) -> &'static str { self.name }
+    #[inline]
+    pub fn message(&self) -> &str { &self.message }
+}
+
+impl fmt::Display for ProcessPanic {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "process {} panicked: {}", self.name, self.message)
+    }
+}
@@ -1,16 +1,64 @@
 use std::any::{Any, TypeId};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::slice;
 
 use collection_traits::*;
 use vector::Vector;
 
+use super::components::Components;
+use super::entities::Entities;
+use super::exclusive_process::ExclusiveProcess;
+use super::fn_process::FnProcess;
 use super::process::Process;
 use super::entity_manager::EntityManager;
 
 
+#[derive(Debug, Hash, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProcessHandle(usize);
+
+impl ProcessHandle {
+    #[inline]
+    pub fn id(&self) -> usize { self.0 }
+}
+
+
+pub struct ProcessEntry<P: ?Sized = ProcessLock> {
+    handle: ProcessHandle,
+    type_id: TypeId,
+    enabled: bool,
+    process: Box<P>,
+}
+
+impl<P: ?Sized> ProcessEntry<P> {
+    #[inline]
+    fn new(handle: ProcessHandle, type_id: TypeId, process: Box<P>) -> Self {
+        ProcessEntry {
+            handle: handle,
+            type_id: type_id,
+            enabled: true,
+            process: process,
+        }
+    }
+
+    #[inline]
+    pub fn handle(&self) -> ProcessHandle { self.handle }
+    #[inline]
+    pub fn type_id(&self) -> &TypeId { &self.type_id }
+    #[inline]
+    pub fn is_enabled(&self) -> bool { self.enabled }
+    #[inline]
+    pub fn set_enabled(&mut self, enabled: bool) { self.enabled = enabled; }
+    #[inline]
+    pub fn process(&self) -> &P { &self.process }
+    #[inline]
+    pub fn process_mut(&mut self) -> &mut Box<P> { &mut self.process }
+}
+
+
 pub struct Processes {
-    processes: Vector<(TypeId, Box<ProcessLock>)>,
+    processes: Vector<ProcessEntry>,
+    exclusive_processes: Vector<ProcessEntry<ExclusiveProcessLock>>,
+    next_handle: usize,
 }
 
 unsafe impl Send for Processes {}
@@ -21,95 +69,314 @@ impl Processes {
     pub fn new() -> Self {
         Processes {
             processes: Vector::new(),
+            exclusive_processes: Vector::new(),
+            next_handle: 0usize,
         }
     }
 
     #[inline]
     pub fn len(&self) -> usize {
-        self.processes.len()
+        self.processes.len() + self.exclusive_processes.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty() && self.exclusive_processes.is_empty()
     }
     #[inline]
     pub fn sort(&mut self) {
-        self.processes.sort_by(|&(_, ref a), &(_, ref b)| {
-            a.priority().cmp(&b.priority())
+        self.processes.sort_by(|a, b| {
+            a.process.priority().cmp(&b.process.priority())
+        });
+        self.exclusive_processes.sort_by(|a, b| {
+            a.process.priority().cmp(&b.process.priority())
         });
     }
 
+    #[inline]
+    fn next_handle(&mut self) -> ProcessHandle {
+        let handle = ProcessHandle(self.next_handle);
+        self.next_handle += 1;
+        handle
+    }
+
     #[inline]
     fn index_of(&self, type_id: &TypeId) -> Option<usize> {
-        self.processes.iter().position(|&(t, _)| &t == type_id)
+        self.processes.iter().position(|entry| &entry.type_id == type_id)
+    }
+    #[inline]
+    fn index_of_handle(&self, handle: &ProcessHandle) -> Option<usize> {
+        self.processes.iter().position(|entry| &entry.handle == handle)
+    }
+    #[inline]
+    fn exclusive_index_of(&self, type_id: &TypeId) -> Option<usize> {
+        self.exclusive_processes.iter().position(|entry| &entry.type_id == type_id)
+    }
+    #[inline]
+    fn exclusive_index_of_handle(&self, handle: &ProcessHandle) -> Option<usize> {
+        self.exclusive_processes.iter().position(|entry| &entry.handle == handle)
     }
     pub fn process<T: Process>(&self) -> Option<&Arc<RwLock<T>>> {
         match self.index_of(&TypeId::of::<T>()) {
             Some(index) => Some(unsafe {
-                let &(_, ref process) = self.processes.get_unchecked(index);
-                process.downcast_ref_unchecked::<Arc<RwLock<T>>>()
+                self.processes.get_unchecked(index).process.downcast_ref_unchecked::<Arc<RwLock<T>>>()
             }),
             None => None,
         }
     }
+    pub fn process_by_handle<T: Process>(&self, handle: &ProcessHandle) -> Option<&Arc<RwLock<T>>> {
+        match self.index_of_handle(handle) {
+            Some(index) => {
+                let entry = unsafe { self.processes.get_unchecked(index) };
+
+                if entry.type_id == TypeId::of::<T>() {
+                    Some(unsafe { entry.process.downcast_ref_unchecked::<Arc<RwLock<T>>>() })
+                } else {
+                    None
+                }
+            },
+            None => None,
+        }
+    }
+    #[inline]
+    pub fn handles<T: Process>(&self) -> Vector<ProcessHandle> {
+        let type_id = TypeId::of::<T>();
+        self.processes.iter()
+            .filter(|entry| entry.type_id == type_id)
+            .map(|entry| entry.handle)
+            .collect()
+    }
     #[inline]
     pub fn contains<T: Process>(&self) -> bool {
         self.index_of(&TypeId::of::<T>()).is_some()
     }
+    #[inline]
+    pub fn contains_handle(&self, handle: &ProcessHandle) -> bool {
+        self.index_of_handle(handle).is_some() ||
+        self.exclusive_index_of_handle(handle).is_some()
+    }
 
+    pub fn exclusive_process<T: ExclusiveProcess>(&self) -> Option<&Arc<RwLock<T>>> {
+        match self.exclusive_index_of(&TypeId::of::<T>()) {
+            Some(index) => Some(unsafe {
+                self.exclusive_processes.get_unchecked(index).process.downcast_ref_unchecked::<Arc<RwLock<T>>>()
+            }),
+            None => None,
+        }
+    }
+    pub fn exclusive_process_by_handle<T: ExclusiveProcess>(&self, handle: &ProcessHandle) -> Option<&Arc<RwLock<T>>> {
+        match self.exclusive_index_of_handle(handle) {
+            Some(index) => {
+                let entry = unsafe { self.exclusive_processes.get_unchecked(index) };
+
+                if entry.type_id == TypeId::of::<T>() {
+                    Some(unsafe { entry.process.downcast_ref_unchecked::<Arc<RwLock<T>>>() })
+                } else {
+                    None
+                }
+            },
+            None => None,
+        }
+    }
+    #[inline]
+    pub fn contains_exclusive<T: ExclusiveProcess>(&self) -> bool {
+        self.exclusive_index_of(&TypeId::of::<T>()).is_some()
+    }
+
+    #[inline]
+    pub fn insert<T: Process>(&mut self, process: T) -> ProcessHandle {
+        let handle = self.next_handle();
+
+        self.processes.push(ProcessEntry::new(
+            handle,
+            TypeId::of::<T>(),
+            Box::new(Arc::new(RwLock::new(process)))
+        ));
+
+        handle
+    }
     #[inline]
-    pub fn insert<T: Process>(&mut self, process: T) {
-        self.processes.push((
+    pub fn insert_fn<F>(&mut self, priority: usize, f: F) -> ProcessHandle
+        where F: FnMut(&EntityManager) + Send + Sync + 'static
+    {
+        self.insert(FnProcess::new(priority, f))
+    }
+    #[inline]
+    pub fn insert_exclusive<T: ExclusiveProcess>(&mut self, process: T) -> ProcessHandle {
+        let handle = self.next_handle();
+
+        self.exclusive_processes.push(ProcessEntry::new(
+            handle,
             TypeId::of::<T>(),
             Box::new(Arc::new(RwLock::new(process)))
         ));
+
+        handle
     }
+    #[inline]
     pub fn remove<T: Process>(&mut self) -> Option<T> {
         match self.remove_by_type_id(&TypeId::of::<T>()) {
-            Some(process_lock) => match Arc::try_unwrap(unsafe {
-                *process_lock.downcast_unchecked::<Arc<RwLock<T>>>()
-            }) {
-                Ok(rwlock) => match rwlock.into_inner() {
-                    Ok(process) => Some(process),
-                    Err(..) => None,
-                },
-                Err(..) => None,
-            },
+            Some(process_lock) => Self::into_process(process_lock),
             None => None,
         }
     }
     pub fn remove_by_type_id(&mut self, type_id: &TypeId) -> Option<Box<ProcessLock>> {
         match self.index_of(type_id) {
+            Some(index) => Some(self.processes.remove(index).process),
+            None => None,
+        }
+    }
+    pub fn remove_by_handle(&mut self, handle: &ProcessHandle) -> Option<Box<ProcessLock>> {
+        match self.index_of_handle(handle) {
+            Some(index) => Some(self.processes.remove(index).process),
+            None => None,
+        }
+    }
+    pub fn remove_process_by_handle<T: Process>(&mut self, handle: &ProcessHandle) -> Option<T> {
+        match self.index_of_handle(handle) {
+            Some(index) => if self.processes[index].type_id == TypeId::of::<T>() {
+                Self::into_process(self.processes.remove(index).process)
+            } else {
+                None
+            },
+            None => None,
+        }
+    }
+    pub fn remove_exclusive<T: ExclusiveProcess>(&mut self) -> Option<T> {
+        match self.exclusive_index_of(&TypeId::of::<T>()) {
             Some(index) => {
-                let (_type_id, process) = self.processes.remove(index);
-                Some(process)
+                let process_lock = self.exclusive_processes.remove(index).process;
+                Self::into_inner(unsafe { *process_lock.downcast_unchecked::<Arc<RwLock<T>>>() })
             },
             None => None,
         }
     }
+    pub fn remove_exclusive_by_handle(&mut self, handle: &ProcessHandle) -> Option<Box<ExclusiveProcessLock>> {
+        match self.exclusive_index_of_handle(handle) {
+            Some(index) => Some(self.exclusive_processes.remove(index).process),
+            None => None,
+        }
+    }
+    #[inline]
+    fn into_process<T: Process>(process_lock: Box<ProcessLock>) -> Option<T> {
+        Self::into_inner(unsafe { *process_lock.downcast_unchecked::<Arc<RwLock<T>>>() })
+    }
+    #[inline]
+    fn into_inner<T>(process: Arc<RwLock<T>>) -> Option<T> {
+        match Arc::try_unwrap(process) {
+            Ok(rwlock) => match rwlock.into_inner() {
+                Ok(process) => Some(process),
+                Err(poisoned) => Some(poisoned.into_inner()),
+            },
+            Err(..) => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled<T: Process>(&self) -> bool {
+        match self.index_of(&TypeId::of::<T>()) {
+            Some(index) => self.processes[index].enabled,
+            None => false,
+        }
+    }
+    #[inline]
+    pub fn enable<T: Process>(&mut self) -> bool {
+        self.set_enabled_by_type_id(&TypeId::of::<T>(), true)
+    }
+    #[inline]
+    pub fn disable<T: Process>(&mut self) -> bool {
+        self.set_enabled_by_type_id(&TypeId::of::<T>(), false)
+    }
+    pub fn set_enabled_by_type_id(&mut self, type_id: &TypeId, enabled: bool) -> bool {
+        match self.index_of(type_id) {
+            Some(index) => {
+                self.processes[index].enabled = enabled;
+                true
+            },
+            None => false,
+        }
+    }
+    #[inline]
+    pub fn is_enabled_by_handle(&self, handle: &ProcessHandle) -> bool {
+        match self.index_of_handle(handle) {
+            Some(index) => self.processes[index].enabled,
+            None => match self.exclusive_index_of_handle(handle) {
+                Some(index) => self.exclusive_processes[index].enabled,
+                None => false,
+            },
+        }
+    }
+    pub fn set_enabled_by_handle(&mut self, handle: &ProcessHandle, enabled: bool) -> bool {
+        match self.index_of_handle(handle) {
+            Some(index) => {
+                self.processes[index].enabled = enabled;
+                true
+            },
+            None => match self.exclusive_index_of_handle(handle) {
+                Some(index) => {
+                    self.exclusive_processes[index].enabled = enabled;
+                    true
+                },
+                None => false,
+            },
+        }
+    }
 
     #[inline]
-    pub fn raw(&self) -> &Vector<(TypeId, Box<ProcessLock>)> {
+    pub fn raw(&self) -> &Vector<ProcessEntry> {
         &self.processes
     }
     #[inline]
-    pub fn raw_mut(&mut self) -> &mut Vector<(TypeId, Box<ProcessLock>)> {
+    pub fn raw_mut(&mut self) -> &mut Vector<ProcessEntry> {
         &mut self.processes
     }
+    #[inline]
+    pub fn raw_exclusive(&self) -> &Vector<ProcessEntry<ExclusiveProcessLock>> {
+        &self.exclusive_processes
+    }
+    #[inline]
+    pub fn raw_exclusive_mut(&mut self) -> &mut Vector<ProcessEntry<ExclusiveProcessLock>> {
+        &mut self.exclusive_processes
+    }
 
     #[inline]
-    pub fn iter(&mut self) -> Iter {
+    pub fn iter(&mut self) -> Iter<'_> {
         Iter::new(self.processes.iter())
     }
     #[inline]
-    pub fn iter_mut(&mut self) -> IterMut {
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
         IterMut::new(self.processes.iter_mut())
     }
+    pub fn enabled(&self) -> Vector<(ProcessHandle, Box<ProcessLock>)> {
+        let mut enabled = Vector::with_capacity(self.processes.len());
+
+        for entry in self.processes.iter() {
+            if entry.enabled {
+                enabled.push((entry.handle, entry.process.clone_as_box()));
+            }
+        }
+
+        enabled
+    }
+    pub fn enabled_exclusive(&self) -> Vector<(ProcessHandle, Box<ExclusiveProcessLock>)> {
+        let mut enabled = Vector::with_capacity(self.exclusive_processes.len());
+
+        for entry in self.exclusive_processes.iter() {
+            if entry.enabled {
+                enabled.push((entry.handle, entry.process.clone_as_box()));
+            }
+        }
+
+        enabled
+    }
 }
 
 
 pub struct Iter<'a> {
-    iter: slice::Iter<'a, (TypeId, Box<ProcessLock>)>,
+    iter: slice::Iter<'a, ProcessEntry>,
 }
 impl<'a> Iter<'a> {
     #[inline]
-    fn new(iter: slice::Iter<'a, (TypeId, Box<ProcessLock>)>) -> Self {
+    fn new(iter: slice::Iter<'a, ProcessEntry>) -> Self {
         Iter {
             iter: iter,
         }
@@ -121,7 +388,7 @@ impl<'a> Iterator for Iter<'a> {
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         match self.iter.next() {
-            Some(&(_, ref next)) => Some(next.clone_as_box()),
+            Some(entry) => Some(entry.process.clone_as_box()),
             None => None,
         }
     }
@@ -129,11 +396,11 @@ impl<'a> Iterator for Iter<'a> {
 
 
 pub struct IterMut<'a> {
-    iter: slice::IterMut<'a, (TypeId, Box<ProcessLock>)>,
+    iter: slice::IterMut<'a, ProcessEntry>,
 }
 impl<'a> IterMut<'a> {
     #[inline]
-    fn new(iter: slice::IterMut<'a, (TypeId, Box<ProcessLock>)>) -> Self {
+    fn new(iter: slice::IterMut<'a, ProcessEntry>) -> Self {
         IterMut {
             iter: iter,
         }
@@ -145,7 +412,7 @@ impl<'a> Iterator for IterMut<'a> {
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         match self.iter.next() {
-            Some(&mut (_, ref next)) => Some(next.clone_as_box()),
+            Some(entry) => Some(entry.process.clone_as_box()),
             None => None,
         }
     }
@@ -156,14 +423,37 @@ pub trait ProcessLock: Any + Send + Sync {
     fn run(&mut self, &EntityManager);
     fn clone_as_box(&self) -> Box<ProcessLock>;
     fn priority(&self) -> usize;
+    fn name(&self) -> &'static str;
 }
 
 impl_any!(ProcessLock);
 
+trait ProcessRwLock<T> {
+    fn read_process(&self) -> RwLockReadGuard<'_, T>;
+    fn write_process(&self) -> RwLockWriteGuard<'_, T>;
+}
+
+impl<T> ProcessRwLock<T> for RwLock<T> {
+    #[inline]
+    fn read_process(&self) -> RwLockReadGuard<'_, T> {
+        match self.read() {
+            Ok(process) => process,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+    #[inline]
+    fn write_process(&self) -> RwLockWriteGuard<'_, T> {
+        match self.write() {
+            Ok(process) => process,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
 impl<T: Process> ProcessLock for Arc<RwLock<T>> {
     #[inline]
     fn run(&mut self, entity_manager: &EntityManager) {
-        self.write().unwrap().run(entity_manager);
+        self.write_process().run(entity_manager);
     }
     #[inline]
     fn clone_as_box(&self) -> Box<ProcessLock> {
@@ -171,7 +461,40 @@ impl<T: Process> ProcessLock for Arc<RwLock<T>> {
     }
     #[inline]
     fn priority(&self) -> usize {
-        self.read().unwrap().priority()
+        self.read_process().priority()
+    }
+    #[inline]
+    fn name(&self) -> &'static str {
+        self.read_process().name()
+    }
+}
+
+
+pub trait ExclusiveProcessLock: Any + Send + Sync {
+    fn run(&mut self, &mut Components, &mut Entities);
+    fn clone_as_box(&self) -> Box<ExclusiveProcessLock>;
+    fn priority(&self) -> usize;
+    fn name(&self) -> &'static str;
+}
+
+impl_any!(ExclusiveProcessLock);
+
+impl<T: ExclusiveProcess> ExclusiveProcessLock for Arc<RwLock<T>> {
+    #[inline]
+    fn run(&mut self, components: &mut Components, entities: &mut Entities) {
+        self.write_process().run(components, entities);
+    }
+    #[inline]
+    fn clone_as_box(&self) -> Box<ExclusiveProcessLock> {
+        Box::new(self.clone())
+    }
+    #[inline]
+    fn priority(&self) -> usize {
+        self.read_process().priority()
+    }
+    #[inline]
+    fn name(&self) -> &'static str {
+        self.read_process().name()
     }
 }
 
@@ -194,7 +517,37 @@ mod test {
         let mut processes = Processes::new();
         processes.insert(SomeProcess);
 
+        assert!(processes.is_enabled::<SomeProcess>());
+        assert!(processes.disable::<SomeProcess>());
+        assert!(!processes.is_enabled::<SomeProcess>());
+        assert_eq!(processes.enabled().len(), 0);
+
         let process = processes.remove::<SomeProcess>().unwrap();
         assert_eq!(process, SomeProcess);
     }
+
+
+    #[derive(Debug, Eq, PartialEq)]
+    pub struct CountProcess(usize);
+
+    impl Process for CountProcess {
+        fn run(&mut self, _: &EntityManager) {}
+    }
+
+
+    #[test]
+    fn test_process_handles() {
+        let mut processes = Processes::new();
+        let a = processes.insert(CountProcess(1));
+        let b = processes.insert(CountProcess(2));
+
+        assert_ne!(a, b);
+        assert_eq!(processes.handles::<CountProcess>().len(), 2);
+        assert_eq!(processes.process_by_handle::<CountProcess>(&b).unwrap().read().unwrap().0, 2);
+        assert!(processes.process_by_handle::<SomeProcess>(&b).is_none());
+
+        assert_eq!(processes.remove_process_by_handle::<CountProcess>(&b).unwrap(), CountProcess(2));
+        assert!(!processes.contains_handle(&b));
+        assert_eq!(processes.process::<CountProcess>().unwrap().read().unwrap().0, 1);
+    }
 }
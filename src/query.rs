@@ -0,0 +1,317 @@
+use std::any::Any;
+use std::cmp;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use atomic::Atomic;
+use collection_traits::*;
+use thread_pool::ThreadPool;
+use vector::Vector;
+use waiter::Waiter;
+
+use super::component::Component;
+use super::component_manager::ComponentManager;
+use super::entity::Entity;
+use super::entity_manager::EntityManager;
+
+
+pub struct Query<T: Component> {
+    entity_manager: EntityManager,
+    phantom_data: PhantomData<T>,
+}
+
+impl<T: Component> Query<T> {
+    #[inline]
+    pub fn new(entity_manager: EntityManager) -> Self {
+        Query {
+            entity_manager: entity_manager,
+            phantom_data: PhantomData,
+        }
+    }
+
+    // only entities alive in the entity manager are visited
+    pub fn entities(&self) -> Vector<Entity> {
+        let components = self.entity_manager.components();
+        let entities = self.entity_manager.entities();
+
+        match components.get_component_manager::<T>() {
+            Some(component_manager) => match component_manager.read() {
+                Ok(component_manager) => component_manager.iter()
+                    .filter(|&(entity, _)| entities.is_alive(&entity))
+                    .map(|(entity, _)| entity)
+                    .collect(),
+                Err(..) => Vector::new(),
+            },
+            None => Vector::new(),
+        }
+    }
+
+    // the entities lock is released before f is called so f can create and remove entities,
+    // f still runs under a shared components lock and must not take components_mut
+    pub fn for_each<F>(&self, mut f: F)
+        where F: FnMut(&Entity, &Atomic<T>)
+    {
+        let components = self.entity_manager.components();
+
+        if let Some(component_manager) = components.get_component_manager::<T>() {
+            if let Ok(component_manager) = component_manager.read() {
+                for (entity, component) in alive(&self.entity_manager, component_manager.iter()) {
+                    f(&entity, component);
+                }
+            }
+        }
+    }
+
+    // runs on the entity manager's thread pool, or only on the calling thread without one
+    #[inline]
+    pub fn par_for_each<F>(&self, chunk_size: usize, f: F)
+        where F: Fn(&Entity, &Atomic<T>) + Send + Sync + 'static
+    {
+        match self.entity_manager.thread_pool() {
+            Some(thread_pool) => self.run_par_for_each(Some(&**thread_pool), chunk_size, f),
+            None => self.run_par_for_each(None, chunk_size, f),
+        }
+    }
+    #[inline]
+    pub fn par_for_each_in<F>(&self, thread_pool: &ThreadPool, chunk_size: usize, f: F)
+        where F: Fn(&Entity, &Atomic<T>) + Send + Sync + 'static
+    {
+        self.run_par_for_each(Some(thread_pool), chunk_size, f);
+    }
+
+    // must not be called while holding a lock on the entity manager's components,
+    // the calling thread works through chunks too so this is safe to call from a process.
+    // chunks are ranges of the storage, so components inserted or removed by other threads
+    // while it runs may shift between chunks and be skipped or visited twice
+    fn run_par_for_each<F>(&self, thread_pool: Option<&ThreadPool>, chunk_size: usize, f: F)
+        where F: Fn(&Entity, &Atomic<T>) + Send + Sync + 'static
+    {
+        let len = match self.entity_manager.components().get_component_manager::<T>() {
+            Some(component_manager) => match component_manager.read() {
+                Ok(component_manager) => component_manager.len(),
+                Err(..) => 0usize,
+            },
+            None => 0usize,
+        };
+
+        if len == 0usize {
+            return;
+        }
+
+        let chunk_size = cmp::max(chunk_size, 1usize);
+        let chunk_count = (len + chunk_size - 1) / chunk_size;
+        let waiter = Waiter::new_with_count(chunk_count);
+        let par_for_each = Arc::new(ParForEach {
+            entity_manager: self.entity_manager.clone(),
+            chunk_size: chunk_size,
+            chunk_count: chunk_count,
+            next_chunk: AtomicUsize::new(0usize),
+            panic: Mutex::new(None),
+            f: f,
+            phantom_data: PhantomData,
+        });
+
+        if let Some(thread_pool) = thread_pool {
+            for _ in 1..chunk_count {
+                let par_for_each = par_for_each.clone();
+                let waiter = waiter.clone();
+
+                let _ = thread_pool.run(move || {
+                    par_for_each.run(&waiter);
+                });
+            }
+        }
+
+        par_for_each.run(&waiter);
+
+        let _ = waiter.wait();
+
+        let payload = match par_for_each.panic.lock() {
+            Ok(mut panic) => panic.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        if let Some(payload) = payload {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+
+// collects the components of alive entities so the entities lock is released before they are visited
+#[inline]
+fn alive<'a, T, I>(entity_manager: &EntityManager, components: I) -> Vector<(Entity, &'a Atomic<T>)>
+    where T: Component,
+          I: Iterator<Item = (Entity, &'a Atomic<T>)>
+{
+    let entities = entity_manager.entities();
+    components.filter(|&(entity, _)| entities.is_alive(&entity)).collect()
+}
+
+
+struct ParForEach<T: Component, F> {
+    entity_manager: EntityManager,
+    chunk_size: usize,
+    chunk_count: usize,
+    next_chunk: AtomicUsize,
+    panic: Mutex<Option<Box<Any + Send>>>,
+    f: F,
+    phantom_data: PhantomData<T>,
+}
+
+impl<T, F> ParForEach<T, F>
+    where T: Component,
+          F: Fn(&Entity, &Atomic<T>) + Send + Sync + 'static
+{
+    fn run(&self, waiter: &Waiter) {
+        loop {
+            let chunk = self.next_chunk.fetch_add(1usize, Ordering::SeqCst);
+
+            if chunk >= self.chunk_count {
+                break;
+            }
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                self.run_chunk(chunk * self.chunk_size);
+            }));
+
+            if let Err(payload) = result {
+                match self.panic.lock() {
+                    Ok(mut panic) => *panic = Some(payload),
+                    Err(poisoned) => *poisoned.into_inner() = Some(payload),
+                }
+            }
+
+            let _ = waiter.done();
+        }
+    }
+
+    #[inline]
+    fn run_chunk(&self, start: usize) {
+        let components = self.entity_manager.components();
+
+        if let Some(component_manager) = components.get_component_manager::<T>() {
+            if let Ok(component_manager) = component_manager.read() {
+                let chunk = component_manager.iter().skip(start).take(self.chunk_size);
+
+                for (entity, component) in alive(&self.entity_manager, chunk) {
+                    (self.f)(&entity, component);
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use component_manager::{HashMapComponentManager, VecComponentManager};
+
+
+    pub struct Value(usize);
+
+    impl Component for Value {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+    pub struct Packed(usize);
+
+    impl Component for Packed {
+        type ComponentManager = VecComponentManager<Self>;
+    }
+
+
+    #[test]
+    fn test_query_par_for_each() {
+        let thread_pool = ThreadPool::new();
+        let entity_manager = EntityManager::new();
+
+        for i in 0..1024usize {
+            let entity = entity_manager.create_entity();
+            entity_manager.insert_component(entity, Value(i));
+        }
+
+        let sum = Arc::new(AtomicUsize::new(0usize));
+        {
+            let sum = sum.clone();
+            entity_manager.query::<Value>().par_for_each_in(&thread_pool, 64usize, move |_, value| {
+                sum.fetch_add(value.as_ref().0, Ordering::SeqCst);
+            });
+        }
+
+        assert_eq!(sum.load(Ordering::SeqCst), (0..1024usize).sum::<usize>());
+    }
+
+    #[test]
+    fn test_query_par_for_each_thread_pool() {
+        let mut entity_manager = EntityManager::new();
+        entity_manager.set_thread_pool(Arc::new(ThreadPool::new()));
+
+        for i in 0..256usize {
+            let entity = entity_manager.create_entity();
+            entity_manager.insert_component(entity, Value(i));
+        }
+
+        let count = Arc::new(AtomicUsize::new(0usize));
+        {
+            let count = count.clone();
+            entity_manager.query::<Value>().par_for_each(16usize, move |_, _| {
+                count.fetch_add(1usize, Ordering::SeqCst);
+            });
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 256usize);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            entity_manager.query::<Value>().par_for_each(16usize, |_, value| {
+                if value.as_ref().0 == 128usize {
+                    panic!("value 128");
+                }
+            });
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_query_for_each_entities_mut() {
+        let entity_manager = EntityManager::new();
+        let entity = entity_manager.create_entity();
+        entity_manager.insert_component(entity, Value(0usize));
+
+        entity_manager.query::<Value>().for_each(|entity, _| {
+            entity_manager.create_entity();
+            entity_manager.remove_entity(entity);
+        });
+
+        assert!(!entity_manager.is_entity_alive(&entity));
+        assert_eq!(entity_manager.entity_count(), 1usize);
+    }
+
+    #[test]
+    fn test_query_vec_component_manager() {
+        let entity_manager = EntityManager::new();
+
+        for i in 0..256usize {
+            let entity = entity_manager.create_entity();
+            entity_manager.insert_component(entity, Packed(i));
+        }
+        let dead = entity_manager.create_entity();
+        entity_manager.insert_component(dead, Packed(1000usize));
+        entity_manager.entities_mut().remove(&dead);
+
+        let mut sum = 0usize;
+        entity_manager.query::<Packed>().for_each(|_, packed| sum += packed.as_ref().0);
+        assert_eq!(sum, (0..256usize).sum::<usize>());
+
+        let par_sum = Arc::new(AtomicUsize::new(0usize));
+        {
+            let par_sum = par_sum.clone();
+            entity_manager.query::<Packed>().par_for_each_in(&ThreadPool::new(), 10usize, move |_, packed| {
+                par_sum.fetch_add(packed.as_ref().0, Ordering::SeqCst);
+            });
+        }
+        assert_eq!(par_sum.load(Ordering::SeqCst), sum);
+        assert_eq!(entity_manager.query::<Packed>().entities().len(), 256usize);
+    }
+}
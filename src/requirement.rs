@@ -0,0 +1,178 @@
+use std::any::{self, TypeId};
+
+use vector::Vector;
+
+use super::component::Component;
+use super::components::Components;
+use super::entity::Entity;
+use super::error::Error;
+
+
+// a companion component inserted with its default when missing
+#[derive(Clone, Copy)]
+pub struct Requirement {
+    type_id: TypeId,
+    name: &'static str,
+    required: fn() -> Vector<Requirement>,
+    register: fn(&mut Components) -> bool,
+    insert_default: fn(&Components, Entity) -> Result<(), Error>,
+}
+
+impl Requirement {
+    #[inline]
+    pub fn of<T: Component + Default>() -> Self {
+        Requirement {
+            type_id: TypeId::of::<T>(),
+            name: any::type_name::<T>(),
+            required: T::required,
+            register: register::<T>,
+            insert_default: insert_default::<T>,
+        }
+    }
+
+    #[inline]
+    pub fn type_id(&self) -> TypeId { self.type_id }
+    #[inline]
+    pub fn name(&self) -> &'static str { self.name }
+    // the requirements of the required component
+    #[inline]
+    pub fn required(&self) -> Vector<Requirement> { (self.required)() }
+
+    #[inline]
+    pub fn register(&self, components: &mut Components) -> bool {
+        (self.register)(components)
+    }
+    #[inline]
+    pub fn insert_default(&self, components: &Components, entity: Entity) -> Result<(), Error> {
+        (self.insert_default)(components, entity)
+    }
+}
+
+
+#[inline]
+fn register<T: Component>(components: &mut Components) -> bool {
+    components.register::<T>()
+}
+#[inline]
+fn insert_default<T: Component + Default>(components: &Components, entity: Entity) -> Result<(), Error> {
+    components.try_insert(entity, T::default())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use collection_traits::*;
+    use entity_manager::EntityManager;
+    use component_manager::{ComponentManager, HashMapComponentManager};
+    use lifecycle_hooks::LifecycleHooks;
+
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+
+    #[derive(Debug, Default, PartialEq, Eq)]
+    pub struct Transform(i32);
+
+    impl Component for Transform {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct RigidBody;
+
+    impl Component for RigidBody {
+        type ComponentManager = HashMapComponentManager<Self>;
+
+        fn required() -> Vector<Requirement> {
+            let mut required = Vector::new();
+            required.push(Requirement::of::<Transform>());
+            required
+        }
+    }
+
+
+    #[test]
+    fn test_required_components() {
+        let entity_manager = EntityManager::new();
+        let entity = entity_manager.create_entity();
+        let saw_transform = Arc::new(AtomicBool::new(false));
+
+        {
+            let hook_entity_manager = entity_manager.clone();
+            let saw_transform = saw_transform.clone();
+
+            entity_manager.register_component_with::<RigidBody>(LifecycleHooks::new()
+                .on_insert(move |entity, _| {
+                    let transforms = hook_entity_manager.query::<Transform>().entities();
+                    saw_transform.store(transforms.contains(entity), Ordering::SeqCst);
+                }));
+        }
+
+        entity_manager.insert_component(entity, RigidBody);
+        assert_eq!(entity_manager.query::<Transform>().entities().len(), 1);
+        assert!(saw_transform.load(Ordering::SeqCst));
+
+        match entity_manager.try_remove_component::<Transform>(&entity) {
+            Err(Error::RequiredComponent(..)) => (),
+            _ => panic!("expected required component error"),
+        }
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| {
+            entity_manager.remove_component::<Transform>(&entity);
+        })).is_err());
+
+        assert_eq!(entity_manager.remove_component::<RigidBody>(&entity), Some(RigidBody));
+        assert_eq!(entity_manager.remove_component::<Transform>(&entity), Some(Transform(0)));
+    }
+
+    #[test]
+    fn test_required_components_missing() {
+        let entity_manager = EntityManager::new();
+        let entity = entity_manager.create_entity();
+
+        entity_manager.insert_component(entity, RigidBody);
+        entity_manager.components().component_manager::<Transform>().write().unwrap().remove(&entity);
+
+        // nothing to remove, so the requiring RigidBody is not an error
+        assert_eq!(entity_manager.remove_component::<Transform>(&entity), None);
+    }
+
+
+    #[derive(Debug, Default)]
+    pub struct Left;
+
+    impl Component for Left {
+        type ComponentManager = HashMapComponentManager<Self>;
+
+        fn required() -> Vector<Requirement> {
+            let mut required = Vector::new();
+            required.push(Requirement::of::<Right>());
+            required
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct Right;
+
+    impl Component for Right {
+        type ComponentManager = HashMapComponentManager<Self>;
+
+        fn required() -> Vector<Requirement> {
+            let mut required = Vector::new();
+            required.push(Requirement::of::<Left>());
+            required
+        }
+    }
+
+    #[test]
+    fn test_required_components_cycle() {
+        let mut components = Components::new();
+
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| {
+            components.register::<Left>();
+        })).is_err());
+        assert!(!components.is_registered::<Left>());
+        assert!(!components.is_registered::<Right>());
+    }
+}
@@ -0,0 +1,106 @@
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use collection_traits::*;
+use vector::Vector;
+
+use super::entity::Entity;
+
+
+struct Node {
+    entity: Entity,
+    next: *mut Node,
+}
+
+
+// lock free stack of entities waiting to be inserted into Entities,
+// nodes are only popped by draining the whole stack so there is no ABA
+pub struct ReservedEntities {
+    head: AtomicPtr<Node>,
+}
+
+unsafe impl Send for ReservedEntities {}
+unsafe impl Sync for ReservedEntities {}
+
+impl ReservedEntities {
+    #[inline]
+    pub fn new() -> Self {
+        ReservedEntities {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    pub fn push(&self, entity: Entity) {
+        let node = Box::into_raw(Box::new(Node {
+            entity: entity,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.head.load(Ordering::Relaxed);
+
+        loop {
+            unsafe { (*node).next = head; }
+
+            match self.head.compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    pub fn drain(&self) -> Vector<Entity> {
+        let mut node = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut entities = Vector::new();
+
+        while !node.is_null() {
+            let boxed = unsafe { Box::from_raw(node) };
+            entities.push(boxed.entity);
+            node = boxed.next;
+        }
+
+        entities
+    }
+}
+
+impl Drop for ReservedEntities {
+    #[inline]
+    fn drop(&mut self) {
+        self.drain();
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+
+    #[test]
+    fn test_reserved_entities() {
+        let reserved = Arc::new(ReservedEntities::new());
+        let mut handles = Vector::new();
+
+        for _ in 0..4 {
+            let reserved = reserved.clone();
+
+            handles.push(thread::spawn(move || {
+                for _ in 0..256 {
+                    reserved.push(Entity::new());
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(reserved.drain().len(), 1024);
+        assert!(reserved.is_empty());
+    }
+}
@@ -1,37 +1,122 @@
-use std::sync::{Arc, RwLock};
+use std::mem;
+#[cfg(feature = "serde")]
+use std::fs::File;
+#[cfg(feature = "serde")]
+use std::io::{BufReader, BufWriter};
+#[cfg(feature = "serde")]
+use std::path::Path;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock};
 
+use atomic::Atomic;
+use collection_traits::*;
 use thread_pool::ThreadPool;
+use vector::Vector;
 use waiter::Waiter;
 
+use super::component::Component;
+use super::entity::Entity;
 use super::entity_manager::EntityManager;
+#[cfg(feature = "serde")]
+use super::entity_map::EntityMap;
+use super::error::Error;
+use super::process_panic::{PanicPolicy, ProcessPanic};
+use super::snapshot::Snapshot;
 use super::processes::Processes;
 
 
 pub struct Scene {
-    thread_pool: ThreadPool,
+    thread_pool: Arc<ThreadPool>,
     entity_manager: EntityManager,
     processes: Arc<RwLock<Processes>>,
+    panic_policy: PanicPolicy,
 }
 
 impl Scene {
     #[inline]
     pub fn new() -> Arc<Self> {
+        Self::new_with_panic_policy(PanicPolicy::default())
+    }
+    #[inline]
+    pub fn new_with_panic_policy(panic_policy: PanicPolicy) -> Arc<Self> {
+        Self::new_with_entity_manager(EntityManager::new(), panic_policy)
+    }
+    #[inline]
+    pub fn new_with_entity_manager(mut entity_manager: EntityManager, panic_policy: PanicPolicy) -> Arc<Self> {
+        let thread_pool = Arc::new(ThreadPool::new());
+
+        entity_manager.set_thread_pool(thread_pool.clone());
+
         Arc::new(Scene {
-            thread_pool: ThreadPool::new(),
-            entity_manager: EntityManager::new(),
+            thread_pool: thread_pool,
+            entity_manager: entity_manager,
             processes: Arc::new(RwLock::new(Processes::new())),
+            panic_policy: panic_policy,
         })
     }
 
     #[inline]
     pub fn thread_pool(&self) -> &ThreadPool { &self.thread_pool }
+    // the pool is shared with the entity manager, so this is None while the scene is alive
     #[inline]
-    pub fn thread_pool_mut(&mut self) -> &mut ThreadPool { &mut self.thread_pool }
+    #[deprecated(note = "the thread pool is shared with the entity manager, use thread_pool")]
+    pub fn thread_pool_mut(&mut self) -> Option<&mut ThreadPool> { Arc::get_mut(&mut self.thread_pool) }
 
     #[inline]
     pub fn entity_manager(&self) -> &EntityManager { &self.entity_manager }
     #[inline]
-    pub fn processes(&self) -> &RwLock<Processes> { &*self.processes }
+    pub fn processes(&self) -> &RwLock<Processes> { &self.processes }
+
+    #[inline]
+    pub fn panic_policy(&self) -> PanicPolicy { self.panic_policy }
+
+    #[inline]
+    pub fn par_for_each<T, F>(&self, chunk_size: usize, f: F) -> &Self
+        where T: Component,
+              F: Fn(&Entity, &Atomic<T>) + Send + Sync + 'static
+    {
+        self.entity_manager.query::<T>().par_for_each(chunk_size, f);
+        self
+    }
+
+    #[inline]
+    pub fn snapshot(&self) -> Result<Snapshot, Error> {
+        self.entity_manager.snapshot()
+    }
+    #[inline]
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<&Self, Error> {
+        self.entity_manager.restore(snapshot)?;
+        Ok(self)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<&Self, Error> {
+        let writer = BufWriter::new(File::create(path)?);
+
+        match serde_json::to_writer_pretty(writer, &self.entity_manager) {
+            Ok(()) => Ok(self),
+            Err(error) => Err(Error::Serialization(error.to_string())),
+        }
+    }
+    // replaces the scene's entities and serde registered components with the file's contents,
+    // entity names and groups are not saved so loaded entities have none,
+    // every loaded entity gets a new id and the returned map goes from file ids to scene ids,
+    // the whole file is read before anything is replaced so a bad file leaves the scene as it was
+    #[cfg(feature = "serde")]
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<EntityMap, Error> {
+        let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(File::open(path)?));
+
+        let state = match self.entity_manager.deserialize_state(&mut deserializer) {
+            Ok(state) => state,
+            Err(error) => return Err(Error::Serialization(error.to_string())),
+        };
+        if let Err(error) = deserializer.end() {
+            return Err(Error::Serialization(error.to_string()));
+        }
+
+        self.entity_manager.replace_state(state)?;
+        self.entity_manager.remap_entities()
+    }
 
     #[inline]
     pub fn init(&self) -> &Self {
@@ -39,24 +124,96 @@ impl Scene {
         self
     }
 
-    pub fn update(&self) -> &Self {
-        let waiter = Waiter::new_with_count(self.processes.read().unwrap().len());
+    pub fn update(&self) -> Result<&Self, Error> {
+        let processes = match self.processes.read() {
+            Ok(processes) => processes.enabled(),
+            Err(..) => return Err(Error::Poisoned("processes")),
+        };
+        let waiter = Waiter::new_with_count(processes.len());
+        let panics = Arc::new(Mutex::new(Vector::new()));
 
-        for mut process in self.processes.write().unwrap().iter_mut() {
+        for (handle, mut process) in processes {
             let entity_manager = self.entity_manager.clone();
             let waiter = waiter.clone();
+            let panics = panics.clone();
 
             let _ = self.thread_pool.run(move || {
-                process.run(&entity_manager);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    process.run(&entity_manager);
+                }));
+
+                if let Err(payload) = result {
+                    let panic = ProcessPanic::new(handle, process.name(), &*payload);
+
+                    match panics.lock() {
+                        Ok(mut panics) => panics.push(panic),
+                        Err(poisoned) => poisoned.into_inner().push(panic),
+                    }
+                }
+
                 let _ = waiter.done();
             });
         }
 
         let _ = waiter.wait();
 
-        self.entity_manager.update();
+        let mut panics = match panics.lock() {
+            Ok(mut panics) => mem::replace(&mut *panics, Vector::new()),
+            Err(poisoned) => mem::replace(&mut *poisoned.into_inner(), Vector::new()),
+        };
 
-        self
+        if panics.is_empty() || self.panic_policy != PanicPolicy::Abort {
+            self.run_exclusive(&mut panics)?;
+        }
+
+        if panics.is_empty() {
+            self.entity_manager.try_update()?;
+            Ok(self)
+        } else {
+            match self.panic_policy {
+                PanicPolicy::Abort => (),
+                PanicPolicy::Disable => {
+                    match self.processes.write() {
+                        Ok(mut processes) => for panic in panics.iter() {
+                            processes.set_enabled_by_handle(&panic.handle(), false);
+                        },
+                        Err(..) => return Err(Error::Poisoned("processes")),
+                    }
+                    self.entity_manager.try_update()?;
+                },
+                PanicPolicy::Continue => {
+                    self.entity_manager.try_update()?;
+                },
+            }
+
+            Err(Error::ProcessPanicked(panics))
+        }
+    }
+
+    fn run_exclusive(&self, panics: &mut Vector<ProcessPanic>) -> Result<(), Error> {
+        let processes = match self.processes.read() {
+            Ok(processes) => processes.enabled_exclusive(),
+            Err(..) => return Err(Error::Poisoned("processes")),
+        };
+
+        if processes.is_empty() {
+            return Ok(());
+        }
+
+        let mut components = self.entity_manager.try_components_mut()?;
+        let mut entities = self.entity_manager.try_entities_mut()?;
+
+        for (handle, mut process) in processes {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                process.run(&mut components, &mut entities);
+            }));
+
+            if let Err(payload) = result {
+                panics.push(ProcessPanic::new(handle, process.name(), &*payload));
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -64,7 +221,12 @@ impl Scene {
 #[cfg(test)]
 mod test {
     use super::*;
+    use components::Components;
+    use entities::Entities;
+    use exclusive_process::ExclusiveProcess;
     use process::Process;
+    #[cfg(feature = "serde")]
+    use entity_map::MapEntities;
 
 
     const FRAMES: usize = 1024usize;
@@ -131,7 +293,7 @@ mod test {
         scene.init();
 
         for _ in 0..FRAMES {
-            scene.update();
+            scene.update().unwrap();
         }
 
         {
@@ -148,4 +310,203 @@ mod test {
             assert!(p.process::<Process9>().unwrap().read().unwrap().done);
         }
     }
+
+
+    pub struct PanicProcess;
+
+    impl Process for PanicProcess {
+        fn run(&mut self, _: &EntityManager) {
+            panic!("panic process");
+        }
+    }
+
+
+    #[test]
+    fn test_scene_panic() {
+        let scene = Scene::new_with_panic_policy(PanicPolicy::Disable);
+
+        let handle = {
+            let mut p = scene.processes().write().unwrap();
+            p.insert(Process0::new());
+            p.insert(PanicProcess)
+        };
+
+        scene.init();
+
+        let panics = match scene.update() {
+            Err(Error::ProcessPanicked(panics)) => panics,
+            _ => panic!("expected process panic"),
+        };
+        assert_eq!(panics.len(), 1);
+        assert_eq!(panics[0].handle(), handle);
+        assert!(panics[0].name().ends_with("PanicProcess"));
+        assert_eq!(panics[0].message(), "panic process");
+
+        assert!(scene.update().is_ok());
+
+        let p = scene.processes().read().unwrap();
+        assert!(!p.is_enabled::<PanicProcess>());
+        assert_eq!(p.process::<Process0>().unwrap().read().unwrap().count, 2);
+    }
+
+
+    #[test]
+    fn test_scene_fn_process() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let scene = Scene::new();
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let handle = {
+            let count = count.clone();
+            scene.processes().write().unwrap().insert_fn(0, move |_: &EntityManager| {
+                count.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+
+        scene.init();
+        scene.update().unwrap();
+
+        scene.processes().write().unwrap().set_enabled_by_handle(&handle, false);
+        scene.update().unwrap();
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+
+    pub struct ClearProcess;
+
+    impl ExclusiveProcess for ClearProcess {
+        fn run(&mut self, _: &mut Components, entities: &mut Entities) {
+            entities.clear();
+        }
+    }
+
+
+    #[test]
+    fn test_scene_exclusive_process() {
+        let scene = Scene::new();
+
+        {
+            let mut p = scene.processes().write().unwrap();
+            p.insert(Process0::new());
+            p.insert_exclusive(ClearProcess);
+            // entities created by the parallel batch must be cleared by the exclusive process
+            p.insert_fn(0, |entity_manager: &EntityManager| {
+                entity_manager.create_entity();
+            });
+        }
+
+        scene.entity_manager().create_entity();
+        scene.init();
+        scene.update().unwrap();
+
+        assert_eq!(scene.entity_manager().entity_count(), 0);
+
+        let p = scene.processes().read().unwrap();
+        assert!(p.contains_exclusive::<ClearProcess>());
+        assert_eq!(p.process::<Process0>().unwrap().read().unwrap().count, 1);
+    }
+
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Position(i32, i32);
+
+    #[cfg(feature = "serde")]
+    impl Component for Position {
+        type ComponentManager = ::component_manager::HashMapComponentManager<Self>;
+    }
+
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_scene_save_load() {
+        use std::env;
+
+        let path = env::temp_dir().join("ecs_test_scene_save_load.json");
+        let scene = Scene::new();
+        let entity_manager = scene.entity_manager();
+
+        entity_manager.register_serde_component::<Position>("position");
+
+        let entity = entity_manager.create_entity();
+        entity_manager.insert_component(entity, Position(1, 2));
+
+        scene.save(&path).unwrap();
+        let entity_map = scene.load(&path).unwrap();
+        let _ = ::std::fs::remove_file(&path);
+
+        let loaded = entity_map.get(&entity).unwrap();
+        assert!(!entity_manager.is_entity_alive(&entity));
+        assert!(entity_manager.is_entity_alive(&loaded));
+        assert_eq!(entity_manager.remove_component::<Position>(&loaded), Some(Position(1, 2)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_scene_load_error() {
+        use std::{env, fs};
+
+        let path = env::temp_dir().join("ecs_test_scene_load_error.json");
+        let scene = Scene::new();
+        let entity_manager = scene.entity_manager();
+
+        entity_manager.register_serde_component::<Position>("position");
+
+        let entity = entity_manager.create_entity();
+        entity_manager.insert_component(entity, Position(1, 2));
+
+        fs::write(&path, r#"{"entities": [1], "components": {"position": [[1, "bad"]]}}"#).unwrap();
+        let result = scene.load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert!(result.is_err());
+        assert_eq!(entity_manager.entity_count(), 1);
+        assert!(entity_manager.is_entity_alive(&entity));
+        assert_eq!(entity_manager.remove_component::<Position>(&entity), Some(Position(1, 2)));
+    }
+
+
+    #[cfg(feature = "serde")]
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Target(Entity);
+
+    #[cfg(feature = "serde")]
+    impl Component for Target {
+        type ComponentManager = ::component_manager::HashMapComponentManager<Self>;
+    }
+
+    #[cfg(feature = "serde")]
+    impl MapEntities for Target {
+        fn map_entities(&mut self, entity_map: &EntityMap) {
+            self.0.map_entities(entity_map);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_scene_load_map_entities() {
+        use std::env;
+
+        let path = env::temp_dir().join("ecs_test_scene_load_map_entities.json");
+        let scene = Scene::new();
+        let entity_manager = scene.entity_manager();
+
+        entity_manager.register_serde_component::<Target>("target");
+        entity_manager.register_map_entities_component::<Target>();
+
+        let a = entity_manager.create_entity();
+        let b = entity_manager.create_entity();
+        entity_manager.insert_component(a, Target(b));
+
+        scene.save(&path).unwrap();
+        let entity_map = scene.load(&path).unwrap();
+        let _ = ::std::fs::remove_file(&path);
+
+        let loaded_a = entity_map.get(&a).unwrap();
+        let loaded_b = entity_map.get(&b).unwrap();
+        assert_ne!(loaded_b, b);
+        assert_eq!(entity_manager.remove_component::<Target>(&loaded_a), Some(Target(loaded_b)));
+    }
 }
@@ -0,0 +1,588 @@
+use std::any::{Any, TypeId};
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::RwLock;
+
+use collection_traits::*;
+use hash_map::HashMap;
+
+use bincode;
+use erased_serde;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::ser::{self, SerializeMap, SerializeSeq, SerializeStruct};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use super::component::Component;
+use super::component_manager::{ComponentManager, HashMapComponentManager, VecComponentManager, WrappedComponentManager};
+use super::components::{Components, ComponentManagerLock};
+use super::entity::Entity;
+use super::entity_manager::EntityManager;
+use super::error::Error;
+
+
+pub type SerializeFn = fn(&ComponentManagerLock, &mut FnMut(&erased_serde::Serialize));
+pub type DeserializeFn = fn(&mut erased_serde::Deserializer) -> Result<Box<Any + Send>, erased_serde::Error>;
+pub type EncodeFn = fn(&ComponentManagerLock) -> Result<Vec<u8>, Error>;
+pub type DecodeFn = fn(&[u8]) -> Result<Box<Any + Send>, Error>;
+pub type ReplaceFn = fn(&ComponentManagerLock, Box<Any + Send>);
+// upgrades an encoded component storage from one schema version to the next
+pub type Migration = Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync;
+
+
+#[derive(Clone, Copy)]
+pub struct SerdeComponent {
+    name: &'static str,
+    version: u32,
+    serialize: SerializeFn,
+    deserialize: DeserializeFn,
+    encode: EncodeFn,
+    decode: DecodeFn,
+    replace: ReplaceFn,
+}
+
+impl SerdeComponent {
+    #[inline]
+    pub fn name(&self) -> &'static str { self.name }
+    #[inline]
+    pub fn version(&self) -> u32 { self.version }
+    #[inline]
+    pub fn encode(&self, component_manager: &ComponentManagerLock) -> Result<Vec<u8>, Error> {
+        (self.encode)(component_manager)
+    }
+    // decodes a payload into owned components without touching any storage
+    #[inline]
+    pub fn decode(&self, bytes: &[u8]) -> Result<Box<Any + Send>, Error> {
+        (self.decode)(bytes)
+    }
+    // replaces the storage's contents with components returned by decode
+    #[inline]
+    pub fn replace(&self, component_manager: &ComponentManagerLock, decoded: Box<Any + Send>) {
+        (self.replace)(component_manager, decoded)
+    }
+}
+
+
+pub struct SerdeRegistry {
+    names: HashMap<&'static str, TypeId>,
+    components: HashMap<TypeId, SerdeComponent>,
+    migrations: HashMap<(TypeId, u32), Box<Migration>>,
+}
+
+unsafe impl Send for SerdeRegistry {}
+unsafe impl Sync for SerdeRegistry {}
+
+impl SerdeRegistry {
+    #[inline]
+    pub fn new() -> Self {
+        SerdeRegistry {
+            names: HashMap::new(),
+            components: HashMap::new(),
+            migrations: HashMap::new(),
+        }
+    }
+
+    // panics if name is already used by another component type, saved files could not tell them apart
+    pub fn insert<T>(&mut self, name: &'static str, version: u32)
+        where T: Component + Serialize + DeserializeOwned
+    {
+        let type_id = TypeId::of::<T>();
+
+        if let Some(bound) = self.names.get(&name) {
+            if *bound != type_id {
+                panic!("serde name {:?} is already registered for another component", name);
+            }
+        }
+        if let Some(serde_component) = self.components.remove(&type_id) {
+            self.names.remove(&serde_component.name);
+        }
+
+        self.names.insert(name, type_id);
+        self.components.insert(type_id, SerdeComponent {
+            name: name,
+            version: version,
+            serialize: serialize_component::<T>,
+            deserialize: deserialize_component::<T>,
+            encode: encode_component::<T>,
+            decode: decode_component::<T>,
+            replace: replace_component::<T>,
+        });
+    }
+    #[inline]
+    pub fn insert_migration<T, F>(&mut self, from_version: u32, migration: F) -> bool
+        where T: Component,
+              F: Fn(&[u8]) -> Result<Vec<u8>, Error> + Send + Sync + 'static
+    {
+        let type_id = TypeId::of::<T>();
+
+        if self.components.contains_key(&type_id) {
+            self.migrations.insert((type_id, from_version), Box::new(migration));
+            true
+        } else {
+            false
+        }
+    }
+    // runs the migrations from version up to the registered version of the component
+    pub fn migrate(&self, type_id: &TypeId, mut version: u32, mut bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let serde_component = match self.components.get(type_id) {
+            Some(serde_component) => serde_component,
+            None => return Err(Error::Serialization("unknown component type".to_owned())),
+        };
+
+        if version > serde_component.version {
+            return Err(Error::Serialization(format!(
+                "component {} version {} is newer than {}", serde_component.name, version, serde_component.version
+            )));
+        }
+
+        while version < serde_component.version {
+            match self.migrations.get(&(*type_id, version)) {
+                Some(migration) => bytes = migration(&bytes)?,
+                None => return Err(Error::Serialization(format!(
+                    "no migration for component {} from version {}", serde_component.name, version
+                ))),
+            }
+            version += 1;
+        }
+
+        Ok(bytes)
+    }
+
+    #[inline]
+    pub fn type_id(&self, name: &str) -> Option<&TypeId> {
+        self.names.get(name)
+    }
+    #[inline]
+    pub fn name<T: Component>(&self) -> Option<&'static str> {
+        self.components.get(&TypeId::of::<T>()).map(|serde_component| serde_component.name)
+    }
+    #[inline]
+    pub fn get(&self, type_id: &TypeId) -> Option<&SerdeComponent> {
+        self.components.get(type_id)
+    }
+    #[inline]
+    pub fn components(&self) -> &HashMap<TypeId, SerdeComponent> {
+        &self.components
+    }
+    // registered components ordered by name so saves come out the same every time
+    #[inline]
+    pub fn sorted(&self) -> Vec<(&TypeId, &SerdeComponent)> {
+        let mut sorted: Vec<(&TypeId, &SerdeComponent)> = self.components.iter().collect();
+        sorted.sort_by_key(|&(_, serde_component)| serde_component.name);
+        sorted
+    }
+}
+
+
+fn serialize_component<T>(component_manager: &ComponentManagerLock, f: &mut FnMut(&erased_serde::Serialize))
+    where T: Component + Serialize
+{
+    let component_manager = unsafe {
+        component_manager.downcast_ref_unchecked::<RwLock<WrappedComponentManager<T>>>()
+    };
+    let component_manager = match component_manager.read() {
+        Ok(component_manager) => component_manager,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    f(&*component_manager);
+}
+
+fn deserialize_component<T>(deserializer: &mut erased_serde::Deserializer) -> Result<Box<Any + Send>, erased_serde::Error>
+    where T: Component + DeserializeOwned
+{
+    let components: Vec<(Entity, T)> = erased_serde::deserialize(deserializer)?;
+    Ok(Box::new(components))
+}
+
+
+fn encode_component<T>(component_manager: &ComponentManagerLock) -> Result<Vec<u8>, Error>
+    where T: Component + Serialize
+{
+    let component_manager = unsafe {
+        component_manager.downcast_ref_unchecked::<RwLock<WrappedComponentManager<T>>>()
+    };
+    let component_manager = match component_manager.read() {
+        Ok(component_manager) => component_manager,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    bincode::serialize(&*component_manager).map_err(|error| Error::Serialization(error.to_string()))
+}
+
+fn decode_component<T>(bytes: &[u8]) -> Result<Box<Any + Send>, Error>
+    where T: Component + DeserializeOwned
+{
+    match bincode::deserialize::<Vec<(Entity, T)>>(bytes) {
+        Ok(components) => Ok(Box::new(components)),
+        Err(error) => Err(Error::Serialization(error.to_string())),
+    }
+}
+
+fn replace_component<T>(component_manager: &ComponentManagerLock, decoded: Box<Any + Send>)
+    where T: Component
+{
+    let components = match decoded.downcast::<Vec<(Entity, T)>>() {
+        Ok(components) => components,
+        Err(..) => return,
+    };
+    let component_manager = unsafe {
+        component_manager.downcast_ref_unchecked::<RwLock<WrappedComponentManager<T>>>()
+    };
+    let mut component_manager = match component_manager.write() {
+        Ok(component_manager) => component_manager,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    component_manager.clear();
+    for (entity, component) in *components {
+        component_manager.insert(entity, component);
+    }
+}
+
+
+fn serialize_component_manager<T, M, S>(component_manager: &M, serializer: S) -> Result<S::Ok, S::Error>
+    where T: Component + Serialize,
+          M: ComponentManager<T>,
+          S: Serializer
+{
+    let mut entities = component_manager.entities();
+    entities.sort_by(|a, b| a.id().cmp(&b.id()));
+    let mut seq = serializer.serialize_seq(Some(entities.len()))?;
+
+    for entity in entities.iter() {
+        if let Some(component) = component_manager.get(entity) {
+            seq.serialize_element(&(entity, component.as_ref()))?;
+        }
+    }
+
+    seq.end()
+}
+
+struct ComponentManagerVisitor<T, M> {
+    phantom_data: PhantomData<(T, M)>,
+}
+
+impl<'de, T, M> Visitor<'de> for ComponentManagerVisitor<T, M>
+    where T: Component + Deserialize<'de>,
+          M: ComponentManager<T>
+{
+    type Value = M;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of entity and component pairs")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<M, A::Error> {
+        let mut component_manager = M::new();
+
+        while let Some((entity, component)) = seq.next_element::<(Entity, T)>()? {
+            component_manager.insert(entity, component);
+        }
+
+        Ok(component_manager)
+    }
+}
+
+macro_rules! impl_serde_component_manager {
+    ($name: ident) => (
+        impl<T: Component + Serialize> Serialize for $name<T> {
+            #[inline]
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serialize_component_manager::<T, Self, S>(self, serializer)
+            }
+        }
+        impl<'de, T: Component + Deserialize<'de>> Deserialize<'de> for $name<T> {
+            #[inline]
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer.deserialize_seq(ComponentManagerVisitor {
+                    phantom_data: PhantomData,
+                })
+            }
+        }
+    );
+}
+
+impl_serde_component_manager!(HashMapComponentManager);
+impl_serde_component_manager!(VecComponentManager);
+impl_serde_component_manager!(WrappedComponentManager);
+
+
+struct SerializeComponents<'a> {
+    components: &'a Components,
+}
+
+impl<'a> Serialize for SerializeComponents<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let component_managers = self.components.component_managers();
+        let serde_components = self.components.serde_registry().sorted();
+        let len = serde_components.iter()
+            .filter(|&&(type_id, _)| component_managers.contains_key(type_id))
+            .count();
+        let mut map = serializer.serialize_map(Some(len))?;
+
+        for (type_id, serde_component) in serde_components {
+            if let Some(component_manager) = component_managers.get(type_id) {
+                let mut result = Ok(());
+
+                map.serialize_key(serde_component.name)?;
+                (serde_component.serialize)(&**component_manager, &mut |value| {
+                    result = map.serialize_value(value);
+                });
+                result?;
+            }
+        }
+
+        map.end()
+    }
+}
+
+
+struct ComponentsSeed<'a> {
+    components: &'a Components,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for ComponentsSeed<'a> {
+    type Value = Vec<(TypeId, Box<Any + Send>)>;
+
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for ComponentsSeed<'a> {
+    type Value = Vec<(TypeId, Box<Any + Send>)>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of component names to component storages")
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let registry = self.components.serde_registry();
+        let component_managers = self.components.component_managers();
+        let mut decoded = Vec::new();
+
+        while let Some(name) = map.next_key::<String>()? {
+            let type_id = match registry.type_id(&name) {
+                Some(type_id) => type_id,
+                None => return Err(de::Error::custom(format!("unknown component {}", name))),
+            };
+            if !component_managers.contains_key(type_id) {
+                return Err(de::Error::custom(format!("unregistered component {}", name)));
+            }
+            let serde_component = match registry.get(type_id) {
+                Some(serde_component) => serde_component,
+                None => return Err(de::Error::custom(format!("unknown component {}", name))),
+            };
+
+            let components = map.next_value_seed(ComponentSeed {
+                deserialize: serde_component.deserialize,
+            })?;
+            decoded.push((*type_id, components));
+        }
+
+        Ok(decoded)
+    }
+}
+
+
+struct ComponentSeed {
+    deserialize: DeserializeFn,
+}
+
+impl<'de> DeserializeSeed<'de> for ComponentSeed {
+    type Value = Box<Any + Send>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let mut deserializer = <erased_serde::Deserializer>::erase(deserializer);
+        (self.deserialize)(&mut deserializer).map_err(de::Error::custom)
+    }
+}
+
+
+impl Serialize for EntityManager {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let components = self.try_components().map_err(ser::Error::custom)?;
+        let entities = self.try_entities().map_err(ser::Error::custom)?;
+        let mut state = serializer.serialize_struct("EntityManager", 2)?;
+
+        state.serialize_field("entities", &*entities)?;
+        state.serialize_field("components", &SerializeComponents {
+            components: &*components,
+        })?;
+
+        state.end()
+    }
+}
+
+const FIELDS: &'static [&'static str] = &["entities", "components"];
+
+// a serialized entity manager decoded against the serde registry but not applied yet
+pub struct DeserializedState {
+    entities: Vec<Entity>,
+    components: Vec<(TypeId, Box<Any + Send>)>,
+}
+
+impl EntityManager {
+    // replaces the entities and serde registered component storages with the deserialized state,
+    // the whole input is decoded before anything is replaced so errors leave the entity manager as it was
+    #[inline]
+    pub fn deserialize_in_place<'de, D: Deserializer<'de>>(&self, deserializer: D) -> Result<(), D::Error> {
+        let state = self.deserialize_state(deserializer)?;
+        self.replace_state(state).map_err(de::Error::custom)
+    }
+    // decodes a serialized entity manager without touching this one
+    pub fn deserialize_state<'de, D: Deserializer<'de>>(&self, deserializer: D) -> Result<DeserializedState, D::Error> {
+        let components = self.try_components().map_err(de::Error::custom)?;
+
+        deserializer.deserialize_struct("EntityManager", FIELDS, EntityManagerVisitor {
+            components: &*components,
+        })
+    }
+    // clears the entities and serde registered component storages and fills them from state
+    pub fn replace_state(&self, state: DeserializedState) -> Result<(), Error> {
+        let components = self.try_components_mut()?;
+        let mut entities = self.try_entities_mut()?;
+        let component_managers = components.component_managers();
+        let registry = components.serde_registry();
+
+        for (type_id, _) in registry.components().iter() {
+            if let Some(component_manager) = component_managers.get(type_id) {
+                component_manager.clear()?;
+            }
+        }
+        for (type_id, decoded) in state.components {
+            if let (Some(component_manager), Some(serde_component)) = (component_managers.get(&type_id), registry.get(&type_id)) {
+                serde_component.replace(&**component_manager, decoded);
+            }
+        }
+
+        entities.clear();
+        for entity in state.entities {
+            entities.insert(entity);
+        }
+
+        Ok(())
+    }
+}
+
+
+struct EntityManagerVisitor<'a> {
+    components: &'a Components,
+}
+
+impl<'a, 'de> Visitor<'de> for EntityManagerVisitor<'a> {
+    type Value = DeserializedState;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("struct EntityManager")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let entities = match seq.next_element::<Vec<Entity>>()? {
+            Some(entities) => entities,
+            None => return Err(de::Error::invalid_length(0, &"struct EntityManager with 2 elements")),
+        };
+        let components = match seq.next_element_seed(ComponentsSeed { components: self.components })? {
+            Some(components) => components,
+            None => return Err(de::Error::invalid_length(1, &"struct EntityManager with 2 elements")),
+        };
+
+        Ok(DeserializedState {
+            entities: entities,
+            components: components,
+        })
+    }
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entities = None;
+        let mut components = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "entities" => entities = Some(map.next_value::<Vec<Entity>>()?),
+                "components" => components = Some(map.next_value_seed(ComponentsSeed { components: self.components })?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                },
+            }
+        }
+
+        match (entities, components) {
+            (Some(entities), Some(components)) => Ok(DeserializedState {
+                entities: entities,
+                components: components,
+            }),
+            (None, _) => Err(de::Error::missing_field("entities")),
+            (_, None) => Err(de::Error::missing_field("components")),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json;
+
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Position(i32, i32);
+
+    impl Component for Position {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+
+    fn round_trip<M: ComponentManager<Position> + Serialize + DeserializeOwned>() {
+        let entity = Entity::new();
+        let mut component_manager = M::new();
+        component_manager.insert(entity, Position(1, 2));
+
+        let json = serde_json::to_string(&component_manager).unwrap();
+        let component_manager: M = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(component_manager.len(), 1);
+        assert_eq!(component_manager.get(&entity).unwrap().as_ref(), &Position(1, 2));
+    }
+
+    #[test]
+    fn test_serialize_component_managers() {
+        round_trip::<HashMapComponentManager<Position>>();
+        round_trip::<VecComponentManager<Position>>();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_serde_registry_name_taken() {
+        #[derive(Serialize, Deserialize)]
+        pub struct Velocity(i32, i32);
+
+        impl Component for Velocity {
+            type ComponentManager = HashMapComponentManager<Self>;
+        }
+
+        let mut serde_registry = SerdeRegistry::new();
+        serde_registry.insert::<Position>("position", 0u32);
+        serde_registry.insert::<Velocity>("position", 0u32);
+    }
+
+    #[test]
+    fn test_serialize_entity_manager() {
+        let entity_manager = EntityManager::new();
+        entity_manager.register_serde_component::<Position>("position");
+
+        let entity = entity_manager.create_entity();
+        entity_manager.insert_component(entity, Position(3, 4));
+
+        let json = serde_json::to_string(&entity_manager).unwrap();
+
+        let loaded = EntityManager::new();
+        loaded.register_serde_component::<Position>("position");
+        loaded.create_entity();
+
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        loaded.deserialize_in_place(&mut deserializer).unwrap();
+        deserializer.end().unwrap();
+
+        assert_eq!(loaded.entity_count(), 1);
+        assert!(loaded.is_entity_alive(&entity));
+        assert_eq!(loaded.remove_component::<Position>(&entity), Some(Position(3, 4)));
+    }
+}
@@ -0,0 +1,86 @@
+use std::any::{Any, TypeId};
+
+use collection_traits::*;
+use hash_map::HashMap;
+
+use super::entities::Entities;
+
+
+pub struct Snapshot {
+    entities: Entities,
+    components: HashMap<TypeId, Box<Any + Send + Sync>>,
+    allocator_state: u64,
+}
+
+unsafe impl Send for Snapshot {}
+unsafe impl Sync for Snapshot {}
+
+impl Snapshot {
+    #[inline]
+    pub fn new(entities: Entities, components: HashMap<TypeId, Box<Any + Send + Sync>>, allocator_state: u64) -> Self {
+        Snapshot {
+            entities: entities,
+            components: components,
+            allocator_state: allocator_state,
+        }
+    }
+
+    #[inline]
+    pub fn entities(&self) -> &Entities { &self.entities }
+    #[inline]
+    pub fn components(&self) -> &HashMap<TypeId, Box<Any + Send + Sync>> { &self.components }
+    #[inline]
+    pub fn allocator_state(&self) -> u64 { self.allocator_state }
+
+    #[inline]
+    pub fn contains<T: Any>(&self) -> bool {
+        self.components.contains_key(&TypeId::of::<T>())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Position(i32, i32);
+
+    impl Component for Position {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct Handle(u32);
+
+    impl Component for Handle {
+        type ComponentManager = HashMapComponentManager<Self>;
+    }
+
+
+    #[test]
+    fn test_snapshot() {
+        let entity_manager = EntityManager::new();
+        entity_manager.register_clone_component::<Position>();
+
+        let entity = entity_manager.create_entity();
+        entity_manager.insert_component(entity, Position(1, 2));
+
+        let snapshot = entity_manager.snapshot().unwrap();
+        assert!(snapshot.contains::<Position>());
+
+        let other = entity_manager.create_entity();
+        entity_manager.insert_component(other, Position(3, 4));
+        entity_manager.insert_component(other, Handle(1));
+        entity_manager.remove_component::<Position>(&entity);
+
+        entity_manager.restore(&snapshot).unwrap();
+
+        assert!(entity_manager.is_entity_alive(&entity));
+        assert!(!entity_manager.is_entity_alive(&other));
+        assert_eq!(entity_manager.remove_component::<Position>(&entity), Some(Position(1, 2)));
+        assert_eq!(entity_manager.remove_component::<Position>(&other), None);
+        assert_eq!(entity_manager.remove_component::<Handle>(&other), None);
+    }
+}